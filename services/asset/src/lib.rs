@@ -2,54 +2,174 @@
 mod tests;
 pub mod types;
 
-use std::collections::BTreeMap;
-
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use derive_more::{Display, From};
+use sha2::{Digest, Sha256};
 
 use binding_macro::{cycles, genesis, service, write};
+use common_crypto::{Crypto, Secp256k1};
+use protocol::fixed_codec::FixedCodec;
 use protocol::traits::{ExecutorParams, ServiceSDK, StoreMap};
-use protocol::types::{Address, Hash, ServiceContext};
+use protocol::types::{Address, Hash, Metadata, ServiceContext};
 use protocol::{ProtocolError, ProtocolErrorKind, ProtocolResult};
 
 use crate::types::{
-    ApproveEvent, ApprovePayload, Asset, AssetBalance, CreateAssetPayload, GetAllowancePayload,
-    GetAllowanceResponse, GetAssetPayload, GetBalancePayload, GetBalanceResponse,
-    InitGenesisPayload, TransferEvent, TransferFromEvent, TransferFromPayload, TransferPayload,
+    AcceptIssuerEvent, AcceptIssuerPayload, Allowance, ApproveEvent, ApproveNftEvent,
+    ApproveNftPayload, ApprovePayload, Asset, AssetBalance, AssetTotals, AuditAssetPayload,
+    AuditAssetResponse, BatchTransferPayload, BurnEvent, BurnPayload, CancelIssuerEvent,
+    CancelIssuerPayload, Checkpoint, ClaimDistributionEvent, ClaimDistributionPayload,
+    ClaimHtlcEvent, ClaimHtlcPayload, ClaimVestedEvent, ClaimVestedPayload, Collection,
+    CreateAssetPayload, CreateCollectionPayload, CreateVestingPayload, DecreaseAllowancePayload,
+    DistributePayload, Distribution, FreezeAccountEvent, FreezeAccountPayload, GetAllowancePayload,
+    GetAllowanceResponse, GetAllowancesPayload, GetAssetPayload, GetBalanceAtPayload,
+    GetBalanceAtResponse, GetBalancePayload, GetBalanceResponse, GetBalancesPayload,
+    GetCollectionPayload, GetDistributionPayload, GetDistributionSharePayload,
+    GetDistributionShareResponse, GetFrozenPayload, GetFrozenResponse, GetHoldersPayload,
+    GetHoldersResponse, GetHtlcPayload, GetNftPayload, GetOperatorPayload, GetOperatorResponse,
+    GetPausedPayload, GetPausedResponse, GetPermitNoncePayload, GetPermitNonceResponse,
+    GetSupplyAtPayload, GetSupplyAtResponse, GetVestingPayload, GetVestingsPayload,
+    GetVestingsResponse, Holder, Htlc, HtlcState, IncreaseAllowancePayload, IndexAssetPayload,
+    InitGenesisPayload, IssuancePolicy, IssuanceWindow, ListAssetsPayload, ListAssetsResponse,
+    LockHtlcPayload, MintEvent, MintNftEvent, MintNftPayload, MintPayload, Nft, OwnerOfPayload,
+    OwnerOfResponse, PauseAssetEvent, PauseAssetPayload, PermitMessage, PermitPayload,
+    PredictAssetIdPayload, PredictAssetIdResponse, ProposeIssuerEvent, ProposeIssuerPayload,
+    ReclaimDistributionEvent, ReclaimDistributionPayload, RefundHtlcEvent, RefundHtlcPayload,
+    SetOperatorEvent, SetOperatorPayload, SetTransferHookEvent, SetTransferHookPayload,
+    TokensOfPayload, TokensOfResponse, TransferEvent, TransferFromEvent, TransferFromPayload,
+    TransferHookArgs, TransferHookPayload, TransferNftEvent, TransferNftPayload, TransferPayload,
+    UnfreezeAccountPayload, UnpauseAssetPayload, UpdateAssetInfoEvent, UpdateAssetInfoPayload,
+    Vesting, VestingStatus,
 };
 
+const FROZEN_KEY: &str = "frozen";
+const ALLOWANCE_KEY: &str = "allowance";
+const PERMIT_NONCE_KEY: &str = "permit_nonce";
+const ASSET_NONCE_KEY: &str = "asset_nonce";
+const ASSETS_INDEX_KEY: &str = "assets";
+const ISSUER_INDEX_KEY: &str = "issuer_assets";
+const SYMBOL_INDEX_KEY: &str = "symbol_assets";
+const HOLDERS_INDEX_KEY: &str = "holders";
+const VESTING_KEY: &str = "vesting";
+const VESTING_COUNT_KEY: &str = "vesting_count";
+const BENEFICIARY_VESTINGS_INDEX_KEY: &str = "beneficiary_vestings";
+const HTLC_KEY: &str = "htlc";
+const HTLC_COUNT_KEY: &str = "htlc_count";
+const BALANCE_CHECKPOINTS_KEY: &str = "balance_checkpoints";
+const SUPPLY_CHECKPOINTS_KEY: &str = "supply_checkpoints";
+const DISTRIBUTION_KEY: &str = "distribution";
+const DISTRIBUTION_COUNT_KEY: &str = "distribution_count";
+const DISTRIBUTION_CLAIMED_KEY: &str = "distribution_claimed";
+const COLLECTION_NONCE_KEY: &str = "collection_nonce";
+const NFT_KEY: &str = "nft";
+const NFT_OPERATOR_KEY: &str = "nft_operator";
+const OWNER_TOKENS_INDEX_KEY: &str = "owner_tokens";
+const ISSUANCE_KEY: &str = "issuance";
+const TOTALS_KEY: &str = "totals";
+
+const MAX_PAGE_LIMIT: u64 = 100;
+const MAX_MEMO_LEN: usize = 256;
+const MEMO_CYCLES_PER_BYTE: u64 = 1_00;
+const BATCH_LEG_CYCLES: u64 = 50_00;
+
+// Signed into every permit message, see `PermitMessage`
+pub const PERMIT_DOMAIN: &str = "huobi-chain/asset/permit";
+
+// What a transfer hook returns to let a transfer through
+pub const TRANSFER_HOOK_APPROVED: &str = "true";
+
 pub struct AssetService<SDK> {
-    sdk:    SDK,
-    assets: Box<dyn StoreMap<Hash, Asset>>,
+    sdk:             SDK,
+    assets:          Box<dyn StoreMap<Hash, Asset>>,
+    pending_issuers: Box<dyn StoreMap<Hash, Address>>,
+    paused_assets:   Box<dyn StoreMap<Hash, bool>>,
+    collections:     Box<dyn StoreMap<Hash, Collection>>,
 }
 
 #[service]
 impl<SDK: ServiceSDK> AssetService<SDK> {
     pub fn new(mut sdk: SDK) -> ProtocolResult<Self> {
         let assets: Box<dyn StoreMap<Hash, Asset>> = sdk.alloc_or_recover_map("assets")?;
+        let pending_issuers: Box<dyn StoreMap<Hash, Address>> =
+            sdk.alloc_or_recover_map("pending_issuers")?;
+        let paused_assets: Box<dyn StoreMap<Hash, bool>> =
+            sdk.alloc_or_recover_map("paused_assets")?;
+        let collections: Box<dyn StoreMap<Hash, Collection>> =
+            sdk.alloc_or_recover_map("collections")?;
 
-        Ok(Self { sdk, assets })
+        Ok(Self {
+            sdk,
+            assets,
+            pending_issuers,
+            paused_assets,
+            collections,
+        })
     }
 
     #[genesis]
     fn init_genesis(&mut self, payload: InitGenesisPayload) -> ProtocolResult<()> {
-        let asset = Asset {
-            id:     payload.id,
-            name:   payload.name,
-            symbol: payload.symbol,
-            supply: payload.supply,
-            issuer: payload.issuer.clone(),
-        };
+        for genesis_asset in payload.assets {
+            let id = genesis_asset.id;
 
-        self.assets.insert(asset.id.clone(), asset.clone())?;
+            if self.assets.contains(&id)? {
+                return Err(ServiceError::Exists { id }.into());
+            }
 
-        let asset_balance = AssetBalance {
-            value:     payload.supply,
-            allowance: BTreeMap::new(),
-        };
+            let mut allocated: u128 = 0;
+            for allocation in genesis_asset.allocations.iter() {
+                let (v, overflow) = allocated.overflowing_add(allocation.amount);
+                if overflow {
+                    return Err(ServiceError::U128Overflow.into());
+                }
+                allocated = v;
+            }
+            if allocated != genesis_asset.supply {
+                return Err(ServiceError::GenesisAllocationMismatch {
+                    id,
+                    supply: genesis_asset.supply,
+                    allocated,
+                }
+                .into());
+            }
+            check_supply_policy(
+                genesis_asset.supply,
+                genesis_asset.max_supply,
+                &genesis_asset.issuance,
+            )?;
 
-        self.sdk
-            .set_account_value(&asset.issuer, asset.id, asset_balance)
+            let asset = Asset {
+                id:            id.clone(),
+                name:          genesis_asset.name,
+                symbol:        genesis_asset.symbol,
+                supply:        genesis_asset.supply,
+                issuer:        genesis_asset.issuer,
+                decimals:      genesis_asset.decimals,
+                description:   genesis_asset.description,
+                logo_uri:      genesis_asset.logo_uri,
+                website:       genesis_asset.website,
+                max_supply:    genesis_asset.max_supply,
+                issuance:      genesis_asset.issuance,
+                transfer_hook: None,
+            };
+
+            self.assets.insert(id.clone(), asset.clone())?;
+            self.sdk
+                .set_value(totals_key(&id), AssetTotals::default())?;
+            self._index_asset(&asset)?;
+            self._record_checkpoint(&supply_checkpoints(&id), 0, asset.supply, 0)?;
+
+            for allocation in genesis_asset.allocations {
+                // The same address may be listed more than once
+                let mut asset_balance: AssetBalance = self
+                    .sdk
+                    .get_account_value(&allocation.address, &id)?
+                    .unwrap_or_default();
+                asset_balance.value += allocation.amount;
+
+                self._set_balance(0, &allocation.address, &id, asset_balance)?;
+            }
+        }
+
+        Ok(())
     }
 
     #[cycles(100_00)]
@@ -59,6 +179,58 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         Ok(asset)
     }
 
+    #[cycles(100_00)]
+    #[read]
+    fn predict_asset_id(
+        &self,
+        ctx: ServiceContext,
+        payload: PredictAssetIdPayload,
+    ) -> ProtocolResult<PredictAssetIdResponse> {
+        let nonce = self._asset_nonce(&payload.issuer)?;
+
+        Ok(PredictAssetIdResponse {
+            asset_id: asset_id(&payload.issuer, nonce),
+            issuer: payload.issuer,
+            nonce,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn list_assets(
+        &self,
+        ctx: ServiceContext,
+        payload: ListAssetsPayload,
+    ) -> ProtocolResult<ListAssetsResponse> {
+        // When both filters are given, the issuer index is paged and the symbol
+        // filter applies within the page, so a page may hold less than `limit`.
+        let index = match (&payload.issuer, &payload.symbol) {
+            (Some(issuer), _) => issuer_index(issuer),
+            (None, Some(symbol)) => symbol_index(symbol),
+            (None, None) => assets_index(),
+        };
+
+        let (ids, next_cursor) = self._index_page::<Hash>(&index, payload.cursor, payload.limit)?;
+
+        let mut assets = Vec::with_capacity(ids.len());
+        for id in ids {
+            ctx.sub_cycles(10_00)?;
+
+            let asset = self.assets.get(&id)?;
+            if let Some(symbol) = &payload.symbol {
+                if &asset.symbol != symbol {
+                    continue;
+                }
+            }
+            assets.push(asset);
+        }
+
+        Ok(ListAssetsResponse {
+            assets,
+            next_cursor,
+        })
+    }
+
     #[cycles(100_00)]
     #[read]
     fn get_balance(
@@ -66,6 +238,44 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         ctx: ServiceContext,
         payload: GetBalancePayload,
     ) -> ProtocolResult<GetBalanceResponse> {
+        self._get_balance(payload.asset_id, payload.user)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_balances(
+        &self,
+        ctx: ServiceContext,
+        payload: GetBalancesPayload,
+    ) -> ProtocolResult<Vec<GetBalanceResponse>> {
+        let queries: Vec<(Hash, Address)> = match payload {
+            GetBalancesPayload::ByUser { user, asset_ids } => asset_ids
+                .into_iter()
+                .map(|asset_id| (asset_id, user.clone()))
+                .collect(),
+            GetBalancesPayload::ByAsset { asset_id, users } => users
+                .into_iter()
+                .map(|user| (asset_id.clone(), user))
+                .collect(),
+        };
+
+        let mut balances = Vec::with_capacity(queries.len());
+        for (asset_id, user) in queries {
+            ctx.sub_cycles(10_00)?;
+
+            balances.push(self._get_balance(asset_id, user)?);
+        }
+
+        Ok(balances)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_balance_at(
+        &self,
+        ctx: ServiceContext,
+        payload: GetBalanceAtPayload,
+    ) -> ProtocolResult<GetBalanceAtResponse> {
         if !self.assets.contains(&payload.asset_id)? {
             return Err(ServiceError::NotFoundAsset {
                 id: payload.asset_id,
@@ -73,18 +283,77 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
             .into());
         }
 
-        let asset_balance = self
-            .sdk
-            .get_account_value(&payload.user, &payload.asset_id)?
-            .unwrap_or(AssetBalance {
-                value:     0,
-                allowance: BTreeMap::new(),
+        let balance = self._balance_at(&payload.asset_id, &payload.user, payload.height)?;
+
+        Ok(GetBalanceAtResponse {
+            asset_id: payload.asset_id,
+            user: payload.user,
+            height: payload.height,
+            balance,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_supply_at(
+        &self,
+        ctx: ServiceContext,
+        payload: GetSupplyAtPayload,
+    ) -> ProtocolResult<GetSupplyAtResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        let supply = self._supply_at(&payload.asset_id, payload.height)?;
+
+        Ok(GetSupplyAtResponse {
+            asset_id: payload.asset_id,
+            height: payload.height,
+            supply,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_holders(
+        &self,
+        ctx: ServiceContext,
+        payload: GetHoldersPayload,
+    ) -> ProtocolResult<GetHoldersResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        let index = holders_index(&payload.asset_id);
+        let holder_count = self._index_len(&index)?;
+        let (users, next_cursor) =
+            self._index_page::<Address>(&index, payload.cursor, payload.limit)?;
+
+        let mut holders = Vec::with_capacity(users.len());
+        for user in users {
+            ctx.sub_cycles(10_00)?;
+
+            let asset_balance: AssetBalance = self
+                .sdk
+                .get_account_value(&user, &payload.asset_id)?
+                .ok_or(ServiceError::InconsistentState("Holder balance is none"))?;
+            holders.push(Holder {
+                user,
+                balance: asset_balance.value,
             });
+        }
 
-        Ok(GetBalanceResponse {
+        Ok(GetHoldersResponse {
             asset_id: payload.asset_id,
-            user:     payload.user,
-            balance:  asset_balance.value,
+            holder_count,
+            holders,
+            next_cursor,
         })
     }
 
@@ -95,6 +364,227 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         ctx: ServiceContext,
         payload: GetAllowancePayload,
     ) -> ProtocolResult<GetAllowanceResponse> {
+        self._get_allowance_response(payload)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_allowances(
+        &self,
+        ctx: ServiceContext,
+        payload: GetAllowancesPayload,
+    ) -> ProtocolResult<Vec<GetAllowanceResponse>> {
+        let mut allowances = Vec::with_capacity(payload.allowances.len());
+        for query in payload.allowances {
+            ctx.sub_cycles(10_00)?;
+
+            allowances.push(self._get_allowance_response(query)?);
+        }
+
+        Ok(allowances)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_vesting(
+        &self,
+        ctx: ServiceContext,
+        payload: GetVestingPayload,
+    ) -> ProtocolResult<VestingStatus> {
+        let vesting = self._get_vesting(payload.id)?;
+
+        Ok(vesting_status(vesting, ctx.get_current_height()))
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_vestings(
+        &self,
+        ctx: ServiceContext,
+        payload: GetVestingsPayload,
+    ) -> ProtocolResult<GetVestingsResponse> {
+        let index = beneficiary_vestings_index(&payload.beneficiary);
+        let (ids, next_cursor) = self._index_page::<u64>(&index, payload.cursor, payload.limit)?;
+
+        let mut vestings = Vec::with_capacity(ids.len());
+        for id in ids {
+            ctx.sub_cycles(10_00)?;
+
+            let vesting = self._get_vesting(id)?;
+            vestings.push(vesting_status(vesting, ctx.get_current_height()));
+        }
+
+        Ok(GetVestingsResponse {
+            beneficiary: payload.beneficiary,
+            vestings,
+            next_cursor,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_distribution(
+        &self,
+        ctx: ServiceContext,
+        payload: GetDistributionPayload,
+    ) -> ProtocolResult<Distribution> {
+        self._get_distribution(payload.id)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_distribution_share(
+        &self,
+        ctx: ServiceContext,
+        payload: GetDistributionSharePayload,
+    ) -> ProtocolResult<GetDistributionShareResponse> {
+        let distribution = self._get_distribution(payload.id)?;
+        let share = self._distribution_share(&distribution, &payload.user)?;
+        let claimed: Option<bool> = self
+            .sdk
+            .get_account_value(&payload.user, &distribution_claimed_key(payload.id))?;
+
+        Ok(GetDistributionShareResponse {
+            id: payload.id,
+            user: payload.user,
+            share,
+            claimed: claimed.unwrap_or(false),
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_htlc(&self, ctx: ServiceContext, payload: GetHtlcPayload) -> ProtocolResult<Htlc> {
+        self._get_htlc(payload.id)
+    }
+
+    // `full_scan` recomputes circulating and locked amounts from every balance
+    // and lock instead of the running totals, it's only available in debug
+    // builds.
+    #[cycles(100_00)]
+    #[read]
+    fn audit_asset(
+        &self,
+        ctx: ServiceContext,
+        payload: AuditAssetPayload,
+    ) -> ProtocolResult<AuditAssetResponse> {
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let supply = self.assets.get(&asset_id)?.supply;
+        let totals = if payload.full_scan {
+            if !cfg!(debug_assertions) {
+                return Err(ServiceError::FullScanDisabled.into());
+            }
+            self._scan_totals(&ctx, &asset_id)?
+        } else {
+            self._totals(&asset_id)?
+        };
+
+        let held = totals.circulating.saturating_add(totals.locked);
+        let discrepancy = if supply > held {
+            supply - held
+        } else {
+            held - supply
+        };
+
+        Ok(AuditAssetResponse {
+            asset_id,
+            full_scan: payload.full_scan,
+            supply,
+            circulating: totals.circulating,
+            locked: totals.locked,
+            burned: totals.burned,
+            discrepancy,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_collection(
+        &self,
+        ctx: ServiceContext,
+        payload: GetCollectionPayload,
+    ) -> ProtocolResult<Collection> {
+        self._get_collection(&payload.id)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_nft(&self, ctx: ServiceContext, payload: GetNftPayload) -> ProtocolResult<Nft> {
+        self._get_nft(&payload.collection_id, payload.token_id)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn owner_of(
+        &self,
+        ctx: ServiceContext,
+        payload: OwnerOfPayload,
+    ) -> ProtocolResult<OwnerOfResponse> {
+        let nft = self._get_nft(&payload.collection_id, payload.token_id)?;
+
+        Ok(OwnerOfResponse {
+            collection_id: nft.collection_id,
+            token_id:      nft.token_id,
+            owner:         nft.owner,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn tokens_of(
+        &self,
+        ctx: ServiceContext,
+        payload: TokensOfPayload,
+    ) -> ProtocolResult<TokensOfResponse> {
+        self._get_collection(&payload.collection_id)?;
+
+        let index = owner_tokens_index(&payload.collection_id, &payload.owner);
+        let token_count = self._index_len(&index)?;
+        let (token_ids, next_cursor) =
+            self._index_page::<u64>(&index, payload.cursor, payload.limit)?;
+        ctx.sub_cycles(token_ids.len() as u64 * 10_00)?;
+
+        Ok(TokensOfResponse {
+            collection_id: payload.collection_id,
+            owner: payload.owner,
+            token_count,
+            token_ids,
+            next_cursor,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_operator(
+        &self,
+        ctx: ServiceContext,
+        payload: GetOperatorPayload,
+    ) -> ProtocolResult<GetOperatorResponse> {
+        self._get_collection(&payload.collection_id)?;
+
+        let approved =
+            self._is_operator(&payload.collection_id, &payload.owner, &payload.operator)?;
+
+        Ok(GetOperatorResponse {
+            collection_id: payload.collection_id,
+            owner: payload.owner,
+            operator: payload.operator,
+            approved,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_frozen(
+        &self,
+        ctx: ServiceContext,
+        payload: GetFrozenPayload,
+    ) -> ProtocolResult<GetFrozenResponse> {
         if !self.assets.contains(&payload.asset_id)? {
             return Err(ServiceError::NotFoundAsset {
                 id: payload.asset_id,
@@ -102,27 +592,50 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
             .into());
         }
 
-        let opt_asset_balance: Option<AssetBalance> = self
-            .sdk
-            .get_account_value(&payload.grantor, &payload.asset_id)?;
+        let frozen = self._is_frozen(&payload.asset_id, &payload.account)?;
 
-        if let Some(v) = opt_asset_balance {
-            let allowance = v.allowance.get(&payload.grantee).unwrap_or(&0);
+        Ok(GetFrozenResponse {
+            asset_id: payload.asset_id,
+            account: payload.account,
+            frozen,
+        })
+    }
 
-            Ok(GetAllowanceResponse {
-                asset_id: payload.asset_id,
-                grantor:  payload.grantor,
-                grantee:  payload.grantee,
-                value:    *allowance,
-            })
-        } else {
-            Ok(GetAllowanceResponse {
-                asset_id: payload.asset_id,
-                grantor:  payload.grantor,
-                grantee:  payload.grantee,
-                value:    0,
-            })
+    #[cycles(100_00)]
+    #[read]
+    fn get_permit_nonce(
+        &self,
+        ctx: ServiceContext,
+        payload: GetPermitNoncePayload,
+    ) -> ProtocolResult<GetPermitNonceResponse> {
+        let nonce = self._permit_nonce(&payload.user)?;
+
+        Ok(GetPermitNonceResponse {
+            user: payload.user,
+            nonce,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_paused(
+        &self,
+        ctx: ServiceContext,
+        payload: GetPausedPayload,
+    ) -> ProtocolResult<GetPausedResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
         }
+
+        let paused = self.paused_assets.contains(&payload.asset_id)?;
+
+        Ok(GetPausedResponse {
+            asset_id: payload.asset_id,
+            paused,
+        })
     }
 
     #[cycles(210_00)]
@@ -133,29 +646,52 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         payload: CreateAssetPayload,
     ) -> ProtocolResult<Asset> {
         let caller = ctx.get_caller();
-        let payload_str = serde_json::to_string(&payload).map_err(ServiceError::JsonParse)?;
-
-        let id = Hash::digest(Bytes::from(payload_str + &caller.as_hex()));
+        let nonce = self._asset_nonce(&caller)?;
+        let id = asset_id(&caller, nonce);
 
         if self.assets.contains(&id)? {
             return Err(ServiceError::Exists { id }.into());
         }
+        check_supply_policy(payload.supply, payload.max_supply, &payload.issuance)?;
+
         let asset = Asset {
-            id:     id.clone(),
-            name:   payload.name,
-            symbol: payload.symbol,
-            supply: payload.supply,
-            issuer: caller,
+            id:            id.clone(),
+            name:          payload.name,
+            symbol:        payload.symbol,
+            supply:        payload.supply,
+            issuer:        caller.clone(),
+            decimals:      payload.decimals,
+            description:   payload.description,
+            logo_uri:      payload.logo_uri,
+            website:       payload.website,
+            max_supply:    payload.max_supply,
+            issuance:      payload.issuance,
+            transfer_hook: None,
         };
         self.assets.insert(id, asset.clone())?;
+        self.sdk
+            .set_value(totals_key(&asset.id), AssetTotals::default())?;
+        self._index_asset(&asset)?;
+        self._record_checkpoint(
+            &supply_checkpoints(&asset.id),
+            ctx.get_current_height(),
+            asset.supply,
+            0,
+        )?;
+        self.sdk
+            .set_account_value(&caller, asset_nonce_key(), nonce + 1)?;
 
         let asset_balance = AssetBalance {
-            value:     payload.supply,
-            allowance: BTreeMap::new(),
+            value: payload.supply,
+            ..Default::default()
         };
 
-        self.sdk
-            .set_account_value(&asset.issuer, asset.id.clone(), asset_balance)?;
+        self._set_balance(
+            ctx.get_current_height(),
+            &asset.issuer,
+            &asset.id,
+            asset_balance,
+        )?;
 
         let event_str = serde_json::to_string(&asset).map_err(ServiceError::JsonParse)?;
         ctx.emit_event(event_str)?;
@@ -163,147 +699,2020 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         Ok(asset)
     }
 
+    // Backfills the indexes for assets and balances written before they
+    // existed. Indexes only mirror stored state, so anyone can call it.
     #[cycles(210_00)]
     #[write]
-    fn transfer(&mut self, ctx: ServiceContext, payload: TransferPayload) -> ProtocolResult<()> {
-        let caller = ctx.get_caller();
-        let asset_id = payload.asset_id.clone();
-        let value = payload.value;
-        let to = payload.to;
-
+    fn index_asset(
+        &mut self,
+        ctx: ServiceContext,
+        payload: IndexAssetPayload,
+    ) -> ProtocolResult<()> {
+        let asset_id = payload.asset_id;
         if !self.assets.contains(&asset_id)? {
             return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
         }
 
-        self._transfer(caller.clone(), to.clone(), asset_id.clone(), value)?;
+        let asset = self.assets.get(&asset_id)?;
+        self._index_asset(&asset)?;
 
-        let event = TransferEvent {
-            asset_id,
+        let holders = holders_index(&asset_id);
+        for user in payload.holders {
+            ctx.sub_cycles(10_00)?;
+
+            let asset_balance: AssetBalance = self
+                .sdk
+                .get_account_value(&user, &asset_id)?
+                .unwrap_or_default();
+            if asset_balance.value != 0 {
+                self._index_insert(&holders, user)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn update_asset_info(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UpdateAssetInfoPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let mut asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        asset.description = payload.description.clone();
+        asset.logo_uri = payload.logo_uri.clone();
+        asset.website = payload.website.clone();
+        self.assets.insert(asset_id.clone(), asset)?;
+
+        let event = UpdateAssetInfoEvent {
+            asset_id,
+            description: payload.description,
+            logo_uri: payload.logo_uri,
+            website: payload.website,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn set_transfer_hook(
+        &mut self,
+        ctx: ServiceContext,
+        payload: SetTransferHookPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let mut asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        asset.transfer_hook = payload.hook.clone();
+        self.assets.insert(asset_id.clone(), asset)?;
+
+        let event = SetTransferHookEvent {
+            asset_id,
+            hook: payload.hook,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn transfer(&mut self, ctx: ServiceContext, payload: TransferPayload) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id.clone();
+        let value = payload.value;
+        let to = payload.to;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        charge_memo(&ctx, &payload.memo)?;
+
+        self._transfer(&ctx, caller.clone(), to.clone(), asset_id.clone(), value)?;
+
+        let event = TransferEvent {
+            asset_id,
             from: caller,
             to,
             value,
+            memo: payload.memo,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn batch_transfer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: BatchTransferPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if payload.transfers.is_empty() {
+            return Err(ServiceError::EmptyBatch.into());
+        }
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        // The base cost covers the first leg, every other leg only adds the
+        // cost of its balance updates
+        let extra_legs = payload.transfers.len() as u64 - 1;
+        ctx.sub_cycles(extra_legs.saturating_mul(BATCH_LEG_CYCLES))?;
+
+        // Any failed leg fails the whole tx, so its state changes are reverted
+        for item in payload.transfers {
+            self._transfer(
+                &ctx,
+                caller.clone(),
+                item.to.clone(),
+                asset_id.clone(),
+                item.value,
+            )?;
+
+            let event = TransferEvent {
+                asset_id: asset_id.clone(),
+                from:     caller.clone(),
+                to:       item.to,
+                value:    item.value,
+                memo:     None,
+            };
+            let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+            ctx.emit_event(event_str)?;
+        }
+
+        Ok(())
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn approve(&mut self, ctx: ServiceContext, payload: ApprovePayload) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let to = payload.to;
+
+        self._check_can_approve(&asset_id, &caller, &to)?;
+
+        let allowance = Allowance {
+            value:  payload.value,
+            expiry: payload.expiry,
+        };
+        self._set_allowance(&asset_id, &caller, &to, allowance.clone())?;
+
+        self._emit_approve_event(&ctx, asset_id, caller, to, allowance)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn increase_allowance(
+        &mut self,
+        ctx: ServiceContext,
+        payload: IncreaseAllowancePayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let to = payload.to;
+
+        self._check_can_approve(&asset_id, &caller, &to)?;
+
+        // The expiry set by the last `approve` is kept, an expired allowance has to
+        // be renewed through `approve`
+        let mut allowance = self._get_allowance(&asset_id, &caller, &to)?;
+        if allowance.is_expired(ctx.get_current_height()) {
+            return Err(ServiceError::AllowanceExpired {
+                id:     asset_id,
+                expiry: allowance.expiry.unwrap_or_default(),
+            }
+            .into());
+        }
+        let (v, overflow) = allowance.value.overflowing_add(payload.value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+        allowance.value = v;
+        self._set_allowance(&asset_id, &caller, &to, allowance.clone())?;
+
+        self._emit_approve_event(&ctx, asset_id, caller, to, allowance)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn decrease_allowance(
+        &mut self,
+        ctx: ServiceContext,
+        payload: DecreaseAllowancePayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let to = payload.to;
+
+        self._check_can_approve(&asset_id, &caller, &to)?;
+
+        let mut allowance = self._get_allowance(&asset_id, &caller, &to)?;
+        if allowance.value < payload.value {
+            return Err(ServiceError::LackOfBalance {
+                expect: payload.value,
+                real:   allowance.value,
+            }
+            .into());
+        }
+        allowance.value -= payload.value;
+        self._set_allowance(&asset_id, &caller, &to, allowance.clone())?;
+
+        self._emit_approve_event(&ctx, asset_id, caller, to, allowance)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn permit(&mut self, ctx: ServiceContext, payload: PermitPayload) -> ProtocolResult<()> {
+        if ctx.get_current_height() > payload.deadline {
+            return Err(ServiceError::PermitExpired {
+                deadline: payload.deadline,
+            }
+            .into());
+        }
+
+        let nonce = self._permit_nonce(&payload.grantor)?;
+        if payload.nonce != nonce {
+            return Err(ServiceError::InvalidPermitNonce {
+                expect: nonce,
+                real:   payload.nonce,
+            }
+            .into());
+        }
+
+        let message = PermitMessage {
+            domain:   PERMIT_DOMAIN.to_owned(),
+            chain_id: self._chain_id(&ctx)?,
+            asset_id: payload.asset_id.clone(),
+            grantor:  payload.grantor.clone(),
+            grantee:  payload.grantee.clone(),
+            value:    payload.value,
+            expiry:   payload.expiry,
+            nonce:    payload.nonce,
+            deadline: payload.deadline,
+        };
+        let pubkey = hex::decode(&payload.pubkey).map_err(ServiceError::HexDecode)?;
+        let signature = hex::decode(&payload.signature).map_err(ServiceError::HexDecode)?;
+        let digest = Hash::digest(message.encode_fixed()?);
+
+        Secp256k1::verify_signature(digest.as_bytes().as_ref(), &signature, &pubkey)
+            .map_err(|_| ServiceError::InvalidPermitSignature)?;
+        if Address::from_pubkey_bytes(Bytes::from(pubkey))? != payload.grantor {
+            return Err(ServiceError::InvalidPermitSignature.into());
+        }
+
+        let asset_id = payload.asset_id;
+        let grantor = payload.grantor;
+        let grantee = payload.grantee;
+
+        self._check_can_approve(&asset_id, &grantor, &grantee)?;
+
+        self.sdk
+            .set_account_value(&grantor, permit_nonce_key(), nonce + 1)?;
+
+        let allowance = Allowance {
+            value:  payload.value,
+            expiry: payload.expiry,
+        };
+        self._set_allowance(&asset_id, &grantor, &grantee, allowance.clone())?;
+
+        self._emit_approve_event(&ctx, asset_id, grantor, grantee, allowance)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn transfer_from(
+        &mut self,
+        ctx: ServiceContext,
+        payload: TransferFromPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let sender = payload.sender;
+        let recipient = payload.recipient;
+        let asset_id = payload.asset_id;
+        let value = payload.value;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        charge_memo(&ctx, &payload.memo)?;
+
+        // Sender and recipient are checked by `_transfer`
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &caller)?;
+
+        let mut sender_allowance = self._get_allowance(&asset_id, &sender, &caller)?;
+        if sender_allowance.is_expired(ctx.get_current_height()) {
+            return Err(ServiceError::AllowanceExpired {
+                id:     asset_id,
+                expiry: sender_allowance.expiry.unwrap_or_default(),
+            }
+            .into());
+        }
+        if sender_allowance.value < value {
+            return Err(ServiceError::LackOfBalance {
+                expect: value,
+                real:   sender_allowance.value,
+            }
+            .into());
+        }
+        sender_allowance.value -= value;
+        self._set_allowance(&asset_id, &sender, &caller, sender_allowance)?;
+
+        self._transfer(
+            &ctx,
+            sender.clone(),
+            recipient.clone(),
+            asset_id.clone(),
+            value,
+        )?;
+
+        let event = TransferFromEvent {
+            asset_id,
+            caller,
+            sender,
+            recipient,
+            value,
+            memo: payload.memo,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn mint(&mut self, ctx: ServiceContext, payload: MintPayload) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let value = payload.value;
+        let to = payload.to;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let mut asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        let (supply, overflow) = asset.supply.overflowing_add(value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+        if let Some(max_supply) = asset.max_supply {
+            if supply > max_supply {
+                return Err(ServiceError::ExceedMaxSupply { max_supply, supply }.into());
+            }
+        }
+        if let Some(policy) = asset.issuance.clone() {
+            self._record_issuance(&asset_id, &policy, ctx.get_current_height(), value)?;
+        }
+
+        let mut to_asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(&to, &asset_id)?
+            .unwrap_or_default();
+
+        let (v, overflow) = to_asset_balance.value.overflowing_add(value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+        to_asset_balance.value = v;
+
+        self._set_balance(ctx.get_current_height(), &to, &asset_id, to_asset_balance)?;
+
+        self._record_checkpoint(
+            &supply_checkpoints(&asset_id),
+            ctx.get_current_height(),
+            supply,
+            asset.supply,
+        )?;
+        asset.supply = supply;
+        self.assets.insert(asset_id.clone(), asset)?;
+
+        let event = MintEvent {
+            asset_id,
+            to,
+            value,
         };
         let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
         ctx.emit_event(event_str)
     }
 
-    #[cycles(210_00)]
-    #[write]
-    fn approve(&mut self, ctx: ServiceContext, payload: ApprovePayload) -> ProtocolResult<()> {
-        let caller = ctx.get_caller();
-        let asset_id = payload.asset_id.clone();
-        let value = payload.value;
-        let to = payload.to;
+    #[cycles(210_00)]
+    #[write]
+    fn burn(&mut self, ctx: ServiceContext, payload: BurnPayload) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let value = payload.value;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &caller)?;
+
+        let mut asset = self.assets.get(&asset_id)?;
+
+        let mut caller_asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(&caller, &asset_id)?
+            .unwrap_or_default();
+        let caller_balance = caller_asset_balance.value;
+
+        if caller_balance < value {
+            return Err(ServiceError::LackOfBalance {
+                expect: value,
+                real:   caller_balance,
+            }
+            .into());
+        }
+        caller_asset_balance.value = caller_balance - value;
+
+        // Supply always covers every balance, so this can only fail if the
+        // stored state is already inconsistent.
+        let (supply, overflow) = asset.supply.overflowing_sub(value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+
+        self._set_balance(
+            ctx.get_current_height(),
+            &caller,
+            &asset_id,
+            caller_asset_balance,
+        )?;
+        self._update_totals(&asset_id, |totals| {
+            totals.burned = totals.burned.saturating_add(value)
+        })?;
+
+        self._record_checkpoint(
+            &supply_checkpoints(&asset_id),
+            ctx.get_current_height(),
+            supply,
+            asset.supply,
+        )?;
+        asset.supply = supply;
+        self.assets.insert(asset_id.clone(), asset)?;
+
+        let event = BurnEvent {
+            asset_id,
+            from: caller,
+            value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn create_vesting(
+        &mut self,
+        ctx: ServiceContext,
+        payload: CreateVestingPayload,
+    ) -> ProtocolResult<Vesting> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let value = payload.value;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        if value == 0 || payload.duration == 0 || payload.cliff > payload.duration {
+            return Err(ServiceError::InvalidVesting.into());
+        }
+
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &caller)?;
+        self._check_not_frozen(&asset_id, &payload.beneficiary)?;
+        self._call_transfer_hook(&ctx, &caller, &payload.beneficiary, &asset_id, value)?;
+
+        self._sub_balance(ctx.get_current_height(), &caller, &asset_id, value)?;
+        self._lock(&asset_id, value)?;
+
+        let id: u64 = self.sdk.get_value(&vesting_count_key())?.unwrap_or(0);
+        let vesting = Vesting {
+            id,
+            asset_id,
+            grantor: caller,
+            beneficiary: payload.beneficiary,
+            total: value,
+            claimed: 0,
+            start: payload.start,
+            cliff: payload.cliff,
+            duration: payload.duration,
+        };
+        self.sdk.set_value(vesting_count_key(), id + 1)?;
+        self.sdk.set_value(vesting_key(id), vesting.clone())?;
+        self._index_insert(&beneficiary_vestings_index(&vesting.beneficiary), id)?;
+
+        let event_str = serde_json::to_string(&vesting).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)?;
+
+        Ok(vesting)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn claim_vested(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ClaimVestedPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let mut vesting = self._get_vesting(payload.id)?;
+
+        if vesting.beneficiary != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        self._check_not_paused(&vesting.asset_id)?;
+        self._check_not_frozen(&vesting.asset_id, &caller)?;
+
+        let value = vesting.vested(ctx.get_current_height()) - vesting.claimed;
+        if value == 0 {
+            return Err(ServiceError::NothingToClaim.into());
+        }
+
+        self._unlock(&vesting.asset_id, value)?;
+        self._add_balance(ctx.get_current_height(), &caller, &vesting.asset_id, value)?;
+
+        vesting.claimed += value;
+        self.sdk
+            .set_value(vesting_key(vesting.id), vesting.clone())?;
+
+        let event = ClaimVestedEvent {
+            id: vesting.id,
+            asset_id: vesting.asset_id,
+            beneficiary: caller,
+            value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    // Balances locked in vestings or htlcs count in the holder supply but are
+    // held by nobody, so their share stays unclaimed until the sender reclaims
+    // it after `claim_deadline`.
+    #[cycles(210_00)]
+    #[write]
+    fn distribute(
+        &mut self,
+        ctx: ServiceContext,
+        payload: DistributePayload,
+    ) -> ProtocolResult<Distribution> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let holder_asset_id = payload.holder_asset_id;
+        let value = payload.value;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+        if !self.assets.contains(&holder_asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: holder_asset_id,
+            }
+            .into());
+        }
+
+        // Balances at the current height may still change in this block
+        if value == 0
+            || payload.snapshot_height >= ctx.get_current_height()
+            || payload.claim_deadline < ctx.get_current_height()
+        {
+            return Err(ServiceError::InvalidDistribution.into());
+        }
+        let holder_supply = self._supply_at(&holder_asset_id, payload.snapshot_height)?;
+        if holder_supply == 0 {
+            return Err(ServiceError::InvalidDistribution.into());
+        }
+
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &caller)?;
+
+        self._sub_balance(ctx.get_current_height(), &caller, &asset_id, value)?;
+        self._lock(&asset_id, value)?;
+
+        let id: u64 = self.sdk.get_value(&distribution_count_key())?.unwrap_or(0);
+        let distribution = Distribution {
+            id,
+            asset_id,
+            holder_asset_id,
+            sender: caller,
+            value,
+            snapshot_height: payload.snapshot_height,
+            holder_supply,
+            claimed: 0,
+            claim_deadline: payload.claim_deadline,
+            reclaimed: false,
+        };
+        self.sdk.set_value(distribution_count_key(), id + 1)?;
+        self.sdk
+            .set_value(distribution_key(id), distribution.clone())?;
+
+        let event_str = serde_json::to_string(&distribution).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)?;
+
+        Ok(distribution)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn claim_distribution(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ClaimDistributionPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let mut distribution = self._get_distribution(payload.id)?;
+
+        if ctx.get_current_height() > distribution.claim_deadline {
+            return Err(ServiceError::DistributionClosed {
+                id: distribution.id,
+            }
+            .into());
+        }
+
+        let claimed_key = distribution_claimed_key(distribution.id);
+        let claimed: Option<bool> = self.sdk.get_account_value(&caller, &claimed_key)?;
+        if claimed.unwrap_or(false) {
+            return Err(ServiceError::NothingToClaim.into());
+        }
+
+        let share = self._distribution_share(&distribution, &caller)?;
+        if share == 0 {
+            return Err(ServiceError::NothingToClaim.into());
+        }
+
+        self._check_not_paused(&distribution.asset_id)?;
+        self._check_not_frozen(&distribution.asset_id, &caller)?;
+        self._call_transfer_hook(
+            &ctx,
+            &distribution.sender,
+            &caller,
+            &distribution.asset_id,
+            share,
+        )?;
+
+        self._unlock(&distribution.asset_id, share)?;
+        self._add_balance(
+            ctx.get_current_height(),
+            &caller,
+            &distribution.asset_id,
+            share,
+        )?;
+        self.sdk.set_account_value(&caller, claimed_key, true)?;
+
+        distribution.claimed += share;
+        self.sdk
+            .set_value(distribution_key(distribution.id), distribution.clone())?;
+
+        let event = ClaimDistributionEvent {
+            id:       distribution.id,
+            asset_id: distribution.asset_id,
+            user:     caller,
+            value:    share,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn reclaim_distribution(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ReclaimDistributionPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let mut distribution = self._get_distribution(payload.id)?;
+
+        if distribution.sender != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+        if ctx.get_current_height() <= distribution.claim_deadline {
+            return Err(ServiceError::DistributionNotClosed {
+                id: distribution.id,
+            }
+            .into());
+        }
+
+        let value = distribution.value - distribution.claimed;
+        if distribution.reclaimed || value == 0 {
+            return Err(ServiceError::NothingToClaim.into());
+        }
+
+        self._check_not_paused(&distribution.asset_id)?;
+        self._check_not_frozen(&distribution.asset_id, &caller)?;
+
+        self._unlock(&distribution.asset_id, value)?;
+        self._add_balance(
+            ctx.get_current_height(),
+            &caller,
+            &distribution.asset_id,
+            value,
+        )?;
+
+        distribution.reclaimed = true;
+        self.sdk
+            .set_value(distribution_key(distribution.id), distribution.clone())?;
+
+        let event = ReclaimDistributionEvent {
+            id: distribution.id,
+            asset_id: distribution.asset_id,
+            sender: caller,
+            value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn lock_htlc(&mut self, ctx: ServiceContext, payload: LockHtlcPayload) -> ProtocolResult<Htlc> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let value = payload.value;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        if value == 0 || payload.timeout <= ctx.get_current_height() {
+            return Err(ServiceError::InvalidHtlc.into());
+        }
+
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &caller)?;
+        self._check_not_frozen(&asset_id, &payload.recipient)?;
+        self._call_transfer_hook(&ctx, &caller, &payload.recipient, &asset_id, value)?;
+
+        self._sub_balance(ctx.get_current_height(), &caller, &asset_id, value)?;
+        self._lock(&asset_id, value)?;
+
+        let id: u64 = self.sdk.get_value(&htlc_count_key())?.unwrap_or(0);
+        let htlc = Htlc {
+            id,
+            asset_id,
+            sender: caller,
+            recipient: payload.recipient,
+            value,
+            hashlock: payload.hashlock,
+            timeout: payload.timeout,
+            state: HtlcState::Locked,
+        };
+        self.sdk.set_value(htlc_count_key(), id + 1)?;
+        self.sdk.set_value(htlc_key(id), htlc.clone())?;
+
+        let event_str = serde_json::to_string(&htlc).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)?;
+
+        Ok(htlc)
+    }
+
+    // Anyone knowing the preimage can claim, the value always goes to the
+    // recipient.
+    #[cycles(210_00)]
+    #[write]
+    fn claim_htlc(&mut self, ctx: ServiceContext, payload: ClaimHtlcPayload) -> ProtocolResult<()> {
+        let mut htlc = self._get_htlc(payload.id)?;
+
+        if htlc.state != HtlcState::Locked {
+            return Err(ServiceError::HtlcNotLocked { id: htlc.id }.into());
+        }
+        if ctx.get_current_height() > htlc.timeout {
+            return Err(ServiceError::HtlcTimeout { id: htlc.id }.into());
+        }
+
+        let preimage = hex::decode(&payload.preimage).map_err(ServiceError::HexDecode)?;
+        if sha256(&preimage)? != htlc.hashlock {
+            return Err(ServiceError::InvalidPreimage.into());
+        }
+
+        self._check_not_paused(&htlc.asset_id)?;
+        self._check_not_frozen(&htlc.asset_id, &htlc.recipient)?;
+
+        self._unlock(&htlc.asset_id, htlc.value)?;
+        self._add_balance(
+            ctx.get_current_height(),
+            &htlc.recipient,
+            &htlc.asset_id,
+            htlc.value,
+        )?;
+
+        htlc.state = HtlcState::Claimed;
+        self.sdk.set_value(htlc_key(htlc.id), htlc.clone())?;
+
+        let event = ClaimHtlcEvent {
+            id:        htlc.id,
+            asset_id:  htlc.asset_id,
+            hashlock:  htlc.hashlock,
+            preimage:  payload.preimage,
+            recipient: htlc.recipient,
+            value:     htlc.value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn refund_htlc(
+        &mut self,
+        ctx: ServiceContext,
+        payload: RefundHtlcPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let mut htlc = self._get_htlc(payload.id)?;
+
+        if htlc.sender != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+        if htlc.state != HtlcState::Locked {
+            return Err(ServiceError::HtlcNotLocked { id: htlc.id }.into());
+        }
+        if ctx.get_current_height() <= htlc.timeout {
+            return Err(ServiceError::HtlcNotTimeout { id: htlc.id }.into());
+        }
+
+        self._check_not_paused(&htlc.asset_id)?;
+        self._check_not_frozen(&htlc.asset_id, &caller)?;
+
+        self._unlock(&htlc.asset_id, htlc.value)?;
+        self._add_balance(
+            ctx.get_current_height(),
+            &caller,
+            &htlc.asset_id,
+            htlc.value,
+        )?;
+
+        htlc.state = HtlcState::Refunded;
+        self.sdk.set_value(htlc_key(htlc.id), htlc.clone())?;
+
+        let event = RefundHtlcEvent {
+            id:       htlc.id,
+            asset_id: htlc.asset_id,
+            hashlock: htlc.hashlock,
+            sender:   caller,
+            value:    htlc.value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn create_collection(
+        &mut self,
+        ctx: ServiceContext,
+        payload: CreateCollectionPayload,
+    ) -> ProtocolResult<Collection> {
+        let caller = ctx.get_caller();
+        let nonce: u64 = self
+            .sdk
+            .get_account_value(&caller, &collection_nonce_key())?
+            .unwrap_or(0);
+        let id = collection_id(&caller, nonce);
+
+        if self.collections.contains(&id)? {
+            return Err(ServiceError::Exists { id }.into());
+        }
+
+        let collection = Collection {
+            id:     id.clone(),
+            name:   payload.name,
+            symbol: payload.symbol,
+            issuer: caller.clone(),
+        };
+        self.collections.insert(id, collection.clone())?;
+        self.sdk
+            .set_account_value(&caller, collection_nonce_key(), nonce + 1)?;
+
+        let event_str = serde_json::to_string(&collection).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)?;
+
+        Ok(collection)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn mint_nft(&mut self, ctx: ServiceContext, payload: MintNftPayload) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let collection_id = payload.collection_id;
+        let token_id = payload.token_id;
+
+        let collection = self._get_collection(&collection_id)?;
+        if collection.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        let key = nft_key(&collection_id, token_id);
+        if self.sdk.get_value::<_, Nft>(&key)?.is_some() {
+            return Err(ServiceError::NftExists {
+                collection_id,
+                token_id,
+            }
+            .into());
+        }
+
+        let nft = Nft {
+            collection_id: collection_id.clone(),
+            token_id,
+            owner: payload.to.clone(),
+            uri: payload.uri.clone(),
+            approved: None,
+        };
+        self.sdk.set_value(key, nft)?;
+        self._index_insert(&owner_tokens_index(&collection_id, &payload.to), token_id)?;
+
+        let event = MintNftEvent {
+            collection_id,
+            token_id,
+            to: payload.to,
+            uri: payload.uri,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    // The owner, the approved address of the token and operators of the owner
+    // can transfer a token, the approval is cleared afterwards.
+    #[cycles(210_00)]
+    #[write]
+    fn transfer_nft(
+        &mut self,
+        ctx: ServiceContext,
+        payload: TransferNftPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let collection_id = payload.collection_id;
+        let token_id = payload.token_id;
+        let to = payload.to;
+
+        let mut nft = self._get_nft(&collection_id, token_id)?;
+        let from = nft.owner.clone();
+
+        if from == to {
+            return Err(ServiceError::RecipientIsSender.into());
+        }
+        if caller != from
+            && nft.approved.as_ref() != Some(&caller)
+            && !self._is_operator(&collection_id, &from, &caller)?
+        {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        nft.owner = to.clone();
+        nft.approved = None;
+        self.sdk.set_value(nft_key(&collection_id, token_id), nft)?;
+        self._index_remove(&owner_tokens_index(&collection_id, &from), &token_id)?;
+        self._index_insert(&owner_tokens_index(&collection_id, &to), token_id)?;
+
+        let event = TransferNftEvent {
+            collection_id,
+            token_id,
+            from,
+            to,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn approve_nft(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ApproveNftPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let collection_id = payload.collection_id;
+        let token_id = payload.token_id;
+
+        let mut nft = self._get_nft(&collection_id, token_id)?;
+        let owner = nft.owner.clone();
+
+        if payload.to.as_ref() == Some(&owner) {
+            return Err(ServiceError::ApproveToYourself.into());
+        }
+        if caller != owner && !self._is_operator(&collection_id, &owner, &caller)? {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        nft.approved = payload.to.clone();
+        self.sdk.set_value(nft_key(&collection_id, token_id), nft)?;
+
+        let event = ApproveNftEvent {
+            collection_id,
+            token_id,
+            owner,
+            approved: payload.to,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn set_operator(
+        &mut self,
+        ctx: ServiceContext,
+        payload: SetOperatorPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let collection_id = payload.collection_id;
+        let operator = payload.operator;
+
+        self._get_collection(&collection_id)?;
+
+        if caller == operator {
+            return Err(ServiceError::ApproveToYourself.into());
+        }
+
+        self.sdk.set_account_value(
+            &caller,
+            nft_operator_key(&collection_id, &operator),
+            payload.approved,
+        )?;
+
+        let event = SetOperatorEvent {
+            collection_id,
+            owner: caller,
+            operator,
+            approved: payload.approved,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn propose_issuer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ProposeIssuerPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let new_issuer = payload.new_issuer;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        // A new proposal simply replaces the previous one
+        self.pending_issuers
+            .insert(asset_id.clone(), new_issuer.clone())?;
+
+        let event = ProposeIssuerEvent {
+            asset_id,
+            issuer: caller,
+            new_issuer,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn accept_issuer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: AcceptIssuerPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        if !self.pending_issuers.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundPendingIssuer { id: asset_id }.into());
+        }
+
+        let new_issuer = self.pending_issuers.get(&asset_id)?;
+        if new_issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        let mut asset = self.assets.get(&asset_id)?;
+        let old_issuer = asset.issuer;
+        asset.issuer = new_issuer.clone();
+
+        self.assets.insert(asset_id.clone(), asset)?;
+        self.pending_issuers.remove(&asset_id)?;
+
+        self._index_remove(&issuer_index(&old_issuer), &asset_id)?;
+        self._index_insert(&issuer_index(&new_issuer), asset_id.clone())?;
+
+        let event = AcceptIssuerEvent {
+            asset_id,
+            old_issuer,
+            new_issuer,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn cancel_issuer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: CancelIssuerPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        if !self.pending_issuers.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundPendingIssuer { id: asset_id }.into());
+        }
+
+        let new_issuer = self.pending_issuers.get(&asset_id)?;
+        self.pending_issuers.remove(&asset_id)?;
+
+        let event = CancelIssuerEvent {
+            asset_id,
+            issuer: caller,
+            new_issuer,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn freeze_account(
+        &mut self,
+        ctx: ServiceContext,
+        payload: FreezeAccountPayload,
+    ) -> ProtocolResult<()> {
+        self._set_frozen(ctx, payload, true)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn unfreeze_account(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UnfreezeAccountPayload,
+    ) -> ProtocolResult<()> {
+        self._set_frozen(ctx, payload, false)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn pause_asset(
+        &mut self,
+        ctx: ServiceContext,
+        payload: PauseAssetPayload,
+    ) -> ProtocolResult<()> {
+        self._set_paused(ctx, payload, true)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn unpause_asset(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UnpauseAssetPayload,
+    ) -> ProtocolResult<()> {
+        self._set_paused(ctx, payload, false)
+    }
+
+    fn _set_frozen(
+        &mut self,
+        ctx: ServiceContext,
+        payload: FreezeAccountPayload,
+        frozen: bool,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let account = payload.account;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        self.sdk
+            .set_account_value(&account, frozen_key(&asset_id), frozen)?;
+
+        let event = FreezeAccountEvent {
+            asset_id,
+            account,
+            frozen,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    fn _set_paused(
+        &mut self,
+        ctx: ServiceContext,
+        payload: PauseAssetPayload,
+        paused: bool,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let asset = self.assets.get(&asset_id)?;
+        if asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        if paused {
+            self.paused_assets.insert(asset_id.clone(), true)?;
+        } else if self.paused_assets.contains(&asset_id)? {
+            self.paused_assets.remove(&asset_id)?;
+        }
+
+        let event = PauseAssetEvent { asset_id, paused };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    fn _asset_nonce(&self, issuer: &Address) -> ProtocolResult<u64> {
+        let nonce: Option<u64> = self.sdk.get_account_value(issuer, &asset_nonce_key())?;
+
+        Ok(nonce.unwrap_or(0))
+    }
+
+    fn _chain_id(&self, ctx: &ServiceContext) -> ProtocolResult<Hash> {
+        let metadata_str = self.sdk.read(ctx, None, "metadata", "get_metadata", "")?;
+        let metadata: Metadata =
+            serde_json::from_str(&metadata_str).map_err(ServiceError::JsonParse)?;
+
+        Ok(metadata.chain_id)
+    }
+
+    fn _permit_nonce(&self, user: &Address) -> ProtocolResult<u64> {
+        let nonce: Option<u64> = self.sdk.get_account_value(user, &permit_nonce_key())?;
+
+        Ok(nonce.unwrap_or(0))
+    }
+
+    fn _is_frozen(&self, asset_id: &Hash, account: &Address) -> ProtocolResult<bool> {
+        let frozen: Option<bool> = self.sdk.get_account_value(account, &frozen_key(asset_id))?;
+
+        Ok(frozen.unwrap_or(false))
+    }
+
+    fn _check_not_frozen(&self, asset_id: &Hash, account: &Address) -> ProtocolResult<()> {
+        if self._is_frozen(asset_id, account)? {
+            return Err(ServiceError::AccountFrozen {
+                id:      asset_id.clone(),
+                account: account.clone(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn _check_not_paused(&self, asset_id: &Hash) -> ProtocolResult<()> {
+        if self.paused_assets.contains(asset_id)? {
+            return Err(ServiceError::AssetPaused {
+                id: asset_id.clone(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn _get_balance(&self, asset_id: Hash, user: Address) -> ProtocolResult<GetBalanceResponse> {
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(&user, &asset_id)?
+            .unwrap_or_default();
+
+        Ok(GetBalanceResponse {
+            asset_id,
+            user,
+            balance: asset_balance.value,
+        })
+    }
+
+    // Every balance is written through here to keep the holder index and the
+    // balance checkpoints, only accounts with a non-zero balance are listed as
+    // holders.
+    fn _set_balance(
+        &mut self,
+        height: u64,
+        user: &Address,
+        asset_id: &Hash,
+        mut asset_balance: AssetBalance,
+    ) -> ProtocolResult<()> {
+        self._migrate_legacy_allowance(user, asset_id, &mut asset_balance)?;
+
+        let previous: Option<AssetBalance> = self.sdk.get_account_value(user, asset_id)?;
+        let previous = previous.map_or(0, |previous| previous.value);
+        self._record_checkpoint(
+            &balance_checkpoints(asset_id, user),
+            height,
+            asset_balance.value,
+            previous,
+        )?;
+
+        let value = asset_balance.value;
+        self._update_totals(asset_id, |totals| {
+            totals.circulating = totals
+                .circulating
+                .saturating_sub(previous)
+                .saturating_add(value)
+        })?;
+
+        let holders = holders_index(asset_id);
+        if asset_balance.value == 0 {
+            self._index_remove(&holders, user)?;
+        } else {
+            self._index_insert(&holders, user.clone())?;
+        }
+
+        self.sdk
+            .set_account_value(user, asset_id.clone(), asset_balance)
+    }
+
+    // Assets created before totals were recorded start from their supply, all
+    // of it was held in balances back then.
+    fn _totals(&self, asset_id: &Hash) -> ProtocolResult<AssetTotals> {
+        let totals: Option<AssetTotals> = self.sdk.get_value(&totals_key(asset_id))?;
+        match totals {
+            Some(totals) => Ok(totals),
+            None => Ok(AssetTotals {
+                circulating: self.assets.get(asset_id)?.supply,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn _update_totals<F: FnOnce(&mut AssetTotals)>(
+        &mut self,
+        asset_id: &Hash,
+        f: F,
+    ) -> ProtocolResult<()> {
+        let mut totals = self._totals(asset_id)?;
+        f(&mut totals);
+        self.sdk.set_value(totals_key(asset_id), totals)
+    }
+
+    // Balances moved into vestings, htlcs and distributions
+    fn _lock(&mut self, asset_id: &Hash, value: u128) -> ProtocolResult<()> {
+        self._update_totals(asset_id, |totals| {
+            totals.locked = totals.locked.saturating_add(value)
+        })
+    }
+
+    fn _unlock(&mut self, asset_id: &Hash, value: u128) -> ProtocolResult<()> {
+        self._update_totals(asset_id, |totals| {
+            totals.locked = totals.locked.saturating_sub(value)
+        })
+    }
+
+    // Sums every holder balance and open lock of the asset, only meant for
+    // debugging since the cost grows with the whole state.
+    fn _scan_totals(&self, ctx: &ServiceContext, asset_id: &Hash) -> ProtocolResult<AssetTotals> {
+        let mut totals = AssetTotals {
+            burned: self._totals(asset_id)?.burned,
+            ..Default::default()
+        };
+
+        let holders = holders_index(asset_id);
+        let mut cursor = Some(0);
+        while let Some(start) = cursor {
+            let (users, next_cursor) = self._index_page::<Address>(&holders, start, 0)?;
+            for user in users {
+                ctx.sub_cycles(10_00)?;
+
+                let balance: AssetBalance = self
+                    .sdk
+                    .get_account_value(&user, asset_id)?
+                    .unwrap_or_default();
+                totals.circulating = totals.circulating.saturating_add(balance.value);
+            }
+            cursor = next_cursor;
+        }
+
+        let vesting_count: u64 = self.sdk.get_value(&vesting_count_key())?.unwrap_or(0);
+        for id in 0..vesting_count {
+            ctx.sub_cycles(10_00)?;
+
+            let vesting = self._get_vesting(id)?;
+            if &vesting.asset_id == asset_id {
+                let locked = vesting.total - vesting.claimed;
+                totals.locked = totals.locked.saturating_add(locked);
+            }
+        }
+
+        let htlc_count: u64 = self.sdk.get_value(&htlc_count_key())?.unwrap_or(0);
+        for id in 0..htlc_count {
+            ctx.sub_cycles(10_00)?;
+
+            let htlc = self._get_htlc(id)?;
+            if &htlc.asset_id == asset_id && htlc.state == HtlcState::Locked {
+                totals.locked = totals.locked.saturating_add(htlc.value);
+            }
+        }
+
+        let distribution_count: u64 = self.sdk.get_value(&distribution_count_key())?.unwrap_or(0);
+        for id in 0..distribution_count {
+            ctx.sub_cycles(10_00)?;
+
+            let distribution = self._get_distribution(id)?;
+            if &distribution.asset_id == asset_id && !distribution.reclaimed {
+                let locked = distribution.value - distribution.claimed;
+                totals.locked = totals.locked.saturating_add(locked);
+            }
+        }
+
+        Ok(totals)
+    }
+
+    // Allowances still embedded in a balance record are moved into their own
+    // records, the caller writes the emptied balance record back.
+    fn _migrate_legacy_allowance(
+        &mut self,
+        user: &Address,
+        asset_id: &Hash,
+        asset_balance: &mut AssetBalance,
+    ) -> ProtocolResult<()> {
+        let legacy_allowance = std::mem::take(&mut asset_balance.legacy_allowance);
+        for (grantee, value) in legacy_allowance {
+            let key = allowance_key(asset_id, &grantee);
+            let allowance: Option<Allowance> = self.sdk.get_account_value(user, &key)?;
+            if allowance.is_none() {
+                self.sdk.set_account_value(user, key, Allowance {
+                    value,
+                    expiry: None,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Checkpoints are appended in height order, a later change in the same
+    // block overwrites the last one. A value set before checkpoints existed is
+    // kept as a checkpoint at height 0.
+    fn _record_checkpoint(
+        &mut self,
+        checkpoints: &Hash,
+        height: u64,
+        value: u128,
+        previous: u128,
+    ) -> ProtocolResult<()> {
+        let mut len: u64 = self
+            .sdk
+            .get_value(&index_len_key(checkpoints))?
+            .unwrap_or(0);
+
+        if len == 0 && previous != 0 {
+            self.sdk
+                .set_value(index_elem_key(checkpoints, 0), Checkpoint {
+                    height: 0,
+                    value:  previous,
+                })?;
+            len = 1;
+        }
+
+        if len > 0 {
+            let last: Checkpoint = self
+                .sdk
+                .get_value(&index_elem_key(checkpoints, len - 1))?
+                .ok_or(ServiceError::InconsistentState("Checkpoint is none"))?;
+            if last.height == height {
+                len -= 1;
+            }
+        }
+
+        self.sdk
+            .set_value(index_elem_key(checkpoints, len), Checkpoint {
+                height,
+                value,
+            })?;
+        self.sdk.set_value(index_len_key(checkpoints), len + 1)
+    }
+
+    // Accounts untouched since checkpoints were introduced report their
+    // current balance.
+    fn _balance_at(&self, asset_id: &Hash, user: &Address, height: u64) -> ProtocolResult<u128> {
+        if let Some(balance) = self._checkpoint_at(&balance_checkpoints(asset_id, user), height)? {
+            return Ok(balance);
+        }
+
+        let asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(user, asset_id)?
+            .unwrap_or_default();
+        Ok(asset_balance.value)
+    }
+
+    fn _supply_at(&self, asset_id: &Hash, height: u64) -> ProtocolResult<u128> {
+        if let Some(supply) = self._checkpoint_at(&supply_checkpoints(asset_id), height)? {
+            return Ok(supply);
+        }
+
+        Ok(self.assets.get(asset_id)?.supply)
+    }
+
+    // Returns none if nothing was ever recorded
+    fn _checkpoint_at(&self, checkpoints: &Hash, height: u64) -> ProtocolResult<Option<u128>> {
+        let len: u64 = self
+            .sdk
+            .get_value(&index_len_key(checkpoints))?
+            .unwrap_or(0);
+        if len == 0 {
+            return Ok(None);
+        }
+
+        // Find the last checkpoint not above `height`
+        let (mut low, mut high) = (0, len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let checkpoint: Checkpoint = self
+                .sdk
+                .get_value(&index_elem_key(checkpoints, mid))?
+                .ok_or(ServiceError::InconsistentState("Checkpoint is none"))?;
+            if checkpoint.height <= height {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        if low == 0 {
+            return Ok(Some(0));
+        }
+        let checkpoint: Checkpoint = self
+            .sdk
+            .get_value(&index_elem_key(checkpoints, low - 1))?
+            .ok_or(ServiceError::InconsistentState("Checkpoint is none"))?;
+        Ok(Some(checkpoint.value))
+    }
+
+    fn _check_can_approve(
+        &self,
+        asset_id: &Hash,
+        grantor: &Address,
+        grantee: &Address,
+    ) -> ProtocolResult<()> {
+        if grantor == grantee {
+            return Err(ServiceError::ApproveToYourself.into());
+        }
+
+        if !self.assets.contains(asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: asset_id.clone(),
+            }
+            .into());
+        }
+
+        self._check_not_paused(asset_id)?;
+        self._check_not_frozen(asset_id, grantor)?;
+        self._check_not_frozen(asset_id, grantee)
+    }
+
+    fn _emit_approve_event(
+        &self,
+        ctx: &ServiceContext,
+        asset_id: Hash,
+        grantor: Address,
+        grantee: Address,
+        allowance: Allowance,
+    ) -> ProtocolResult<()> {
+        let event = ApproveEvent {
+            asset_id,
+            grantor,
+            grantee,
+            value: allowance.value,
+            expiry: allowance.expiry,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    fn _get_allowance_response(
+        &self,
+        payload: GetAllowancePayload,
+    ) -> ProtocolResult<GetAllowanceResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        let allowance =
+            self._get_allowance(&payload.asset_id, &payload.grantor, &payload.grantee)?;
+
+        Ok(GetAllowanceResponse {
+            asset_id: payload.asset_id,
+            grantor:  payload.grantor,
+            grantee:  payload.grantee,
+            value:    allowance.value,
+            expiry:   allowance.expiry,
+        })
+    }
+
+    fn _get_allowance(
+        &self,
+        asset_id: &Hash,
+        grantor: &Address,
+        grantee: &Address,
+    ) -> ProtocolResult<Allowance> {
+        let allowance: Option<Allowance> = self
+            .sdk
+            .get_account_value(grantor, &allowance_key(asset_id, grantee))?;
+        if let Some(allowance) = allowance {
+            return Ok(allowance);
+        }
+
+        let asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(grantor, asset_id)?
+            .unwrap_or_default();
+        Ok(Allowance {
+            value:  *asset_balance.legacy_allowance.get(grantee).unwrap_or(&0),
+            expiry: None,
+        })
+    }
+
+    // Zero allowances are kept instead of removed, otherwise a not yet
+    // migrated legacy allowance would show up again.
+    fn _set_allowance(
+        &mut self,
+        asset_id: &Hash,
+        grantor: &Address,
+        grantee: &Address,
+        allowance: Allowance,
+    ) -> ProtocolResult<()> {
+        let mut asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(grantor, asset_id)?
+            .unwrap_or_default();
+        if !asset_balance.legacy_allowance.is_empty() {
+            self._migrate_legacy_allowance(grantor, asset_id, &mut asset_balance)?;
+            self.sdk
+                .set_account_value(grantor, asset_id.clone(), asset_balance)?;
+        }
+
+        self.sdk
+            .set_account_value(grantor, allowance_key(asset_id, grantee), allowance)
+    }
+
+    fn _add_balance(
+        &mut self,
+        height: u64,
+        user: &Address,
+        asset_id: &Hash,
+        value: u128,
+    ) -> ProtocolResult<()> {
+        let mut asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(user, asset_id)?
+            .unwrap_or_default();
+
+        let (v, overflow) = asset_balance.value.overflowing_add(value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+        asset_balance.value = v;
+
+        self._set_balance(height, user, asset_id, asset_balance)
+    }
+
+    fn _sub_balance(
+        &mut self,
+        height: u64,
+        user: &Address,
+        asset_id: &Hash,
+        value: u128,
+    ) -> ProtocolResult<()> {
+        let mut asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(user, asset_id)?
+            .unwrap_or_default();
+
+        if asset_balance.value < value {
+            return Err(ServiceError::LackOfBalance {
+                expect: value,
+                real:   asset_balance.value,
+            }
+            .into());
+        }
+        asset_balance.value -= value;
+
+        self._set_balance(height, user, asset_id, asset_balance)
+    }
+
+    fn _get_vesting(&self, id: u64) -> ProtocolResult<Vesting> {
+        let vesting: Option<Vesting> = self.sdk.get_value(&vesting_key(id))?;
+
+        vesting.ok_or_else(|| ServiceError::NotFoundVesting { id }.into())
+    }
+
+    fn _get_distribution(&self, id: u64) -> ProtocolResult<Distribution> {
+        let distribution: Option<Distribution> = self.sdk.get_value(&distribution_key(id))?;
+
+        distribution.ok_or_else(|| ServiceError::NotFoundDistribution { id }.into())
+    }
+
+    fn _distribution_share(
+        &self,
+        distribution: &Distribution,
+        user: &Address,
+    ) -> ProtocolResult<u128> {
+        let balance = self._balance_at(
+            &distribution.holder_asset_id,
+            user,
+            distribution.snapshot_height,
+        )?;
+
+        // A balance never exceeds the supply, so the share fits in `value`
+        let share = mul_div(distribution.value, balance, distribution.holder_supply)
+            .ok_or(ServiceError::U128Overflow)?;
+        Ok(share)
+    }
+
+    fn _record_issuance(
+        &mut self,
+        asset_id: &Hash,
+        policy: &IssuancePolicy,
+        height: u64,
+        value: u128,
+    ) -> ProtocolResult<()> {
+        let key = issuance_key(asset_id);
+        let period = height / policy.period;
+
+        let window = self
+            .sdk
+            .get_value::<_, IssuanceWindow>(&key)?
+            .filter(|window| window.period == period)
+            .unwrap_or(IssuanceWindow { period, issued: 0 });
 
-        if caller == to {
-            return Err(ServiceError::ApproveToYourself.into());
+        let (issued, overflow) = window.issued.overflowing_add(value);
+        if overflow {
+            return Err(ServiceError::U128Overflow.into());
+        }
+        if issued > policy.limit {
+            return Err(ServiceError::ExceedIssuanceLimit {
+                limit: policy.limit,
+                issued,
+            }
+            .into());
         }
 
-        if !self.assets.contains(&asset_id)? {
-            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        self.sdk.set_value(key, IssuanceWindow { period, issued })
+    }
+
+    fn _get_collection(&self, id: &Hash) -> ProtocolResult<Collection> {
+        if !self.collections.contains(id)? {
+            return Err(ServiceError::NotFoundCollection { id: id.clone() }.into());
         }
 
-        let mut caller_asset_balance: AssetBalance = self
+        self.collections.get(id)
+    }
+
+    fn _get_nft(&self, collection_id: &Hash, token_id: u64) -> ProtocolResult<Nft> {
+        let nft: Option<Nft> = self.sdk.get_value(&nft_key(collection_id, token_id))?;
+
+        nft.ok_or_else(|| {
+            ServiceError::NotFoundNft {
+                collection_id: collection_id.clone(),
+                token_id,
+            }
+            .into()
+        })
+    }
+
+    fn _is_operator(
+        &self,
+        collection_id: &Hash,
+        owner: &Address,
+        operator: &Address,
+    ) -> ProtocolResult<bool> {
+        let approved: Option<bool> = self
             .sdk
-            .get_account_value(&caller, &asset_id)?
-            .unwrap_or(AssetBalance {
-                value:     0,
-                allowance: BTreeMap::new(),
-            });
-        caller_asset_balance
-            .allowance
-            .entry(to.clone())
-            .and_modify(|e| *e = value)
-            .or_insert(value);
+            .get_account_value(owner, &nft_operator_key(collection_id, operator))?;
 
-        self.sdk
-            .set_account_value(&caller, asset_id.clone(), caller_asset_balance)?;
+        Ok(approved.unwrap_or(false))
+    }
 
-        let event = ApproveEvent {
-            asset_id,
-            grantor: caller,
-            grantee: to,
-            value,
+    fn _get_htlc(&self, id: u64) -> ProtocolResult<Htlc> {
+        let htlc: Option<Htlc> = self.sdk.get_value(&htlc_key(id))?;
+
+        htlc.ok_or_else(|| ServiceError::NotFoundHtlc { id }.into())
+    }
+
+    fn _index_asset(&mut self, asset: &Asset) -> ProtocolResult<()> {
+        self._index_insert(&assets_index(), asset.id.clone())?;
+        self._index_insert(&issuer_index(&asset.issuer), asset.id.clone())?;
+        self._index_insert(&symbol_index(&asset.symbol), asset.id.clone())
+    }
+
+    // An index is a set of elements stored under keys derived from the index
+    // hash. Every element has a position in [0, len), removing an element moves
+    // the last one into its place, so no scan is needed to page through it.
+    fn _index_len(&self, index: &Hash) -> ProtocolResult<u64> {
+        let len: Option<u64> = self.sdk.get_value(&index_len_key(index))?;
+        Ok(len.unwrap_or(0))
+    }
+
+    fn _index_page<T: FixedCodec>(
+        &self,
+        index: &Hash,
+        cursor: u64,
+        limit: u64,
+    ) -> ProtocolResult<(Vec<T>, Option<u64>)> {
+        let limit = if limit == 0 || limit > MAX_PAGE_LIMIT {
+            MAX_PAGE_LIMIT
+        } else {
+            limit
         };
-        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
-        ctx.emit_event(event_str)
+
+        let len = self._index_len(index)?;
+        let end = std::cmp::min(cursor.saturating_add(limit), len);
+
+        let mut elems = Vec::new();
+        for pos in cursor..end {
+            let elem: T = self
+                .sdk
+                .get_value(&index_elem_key(index, pos))?
+                .ok_or(ServiceError::InconsistentState("Index element is none"))?;
+            elems.push(elem);
+        }
+
+        let next_cursor = if end < len { Some(end) } else { None };
+        Ok((elems, next_cursor))
     }
 
-    #[cycles(210_00)]
-    #[write]
-    fn transfer_from(
-        &mut self,
-        ctx: ServiceContext,
-        payload: TransferFromPayload,
-    ) -> ProtocolResult<()> {
-        let caller = ctx.get_caller();
-        let sender = payload.sender;
-        let recipient = payload.recipient;
-        let asset_id = payload.asset_id;
-        let value = payload.value;
+    fn _index_insert<T: FixedCodec>(&mut self, index: &Hash, elem: T) -> ProtocolResult<()> {
+        let pos_key = index_pos_key(index, &elem)?;
+        let pos: Option<u64> = self.sdk.get_value(&pos_key)?;
+        if pos.unwrap_or(0) != 0 {
+            return Ok(());
+        }
 
-        if !self.assets.contains(&asset_id)? {
-            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        let len = self._index_len(index)?;
+        self.sdk.set_value(index_elem_key(index, len), elem)?;
+        // Positions are stored one-based, zero marks a removed element
+        self.sdk.set_value(pos_key, len + 1)?;
+        self.sdk.set_value(index_len_key(index), len + 1)
+    }
+
+    fn _index_remove<T: FixedCodec>(&mut self, index: &Hash, elem: &T) -> ProtocolResult<()> {
+        let pos_key = index_pos_key(index, elem)?;
+        let pos = match self.sdk.get_value::<Hash, u64>(&pos_key)? {
+            Some(pos) if pos != 0 => pos,
+            _ => return Ok(()),
+        };
+
+        let last = self
+            ._index_len(index)?
+            .checked_sub(1)
+            .ok_or(ServiceError::InconsistentState("Index is empty"))?;
+        if pos - 1 > last {
+            return Err(ServiceError::InconsistentState("Index position out of range").into());
         }
+        if pos - 1 != last {
+            let last_elem: T = self
+                .sdk
+                .get_value(&index_elem_key(index, last))?
+                .ok_or(ServiceError::InconsistentState("Index element is none"))?;
+            let last_pos_key = index_pos_key(index, &last_elem)?;
 
-        let mut sender_asset_balance: AssetBalance = self
-            .sdk
-            .get_account_value(&sender, &asset_id)?
-            .unwrap_or(AssetBalance {
-                value:     0,
-                allowance: BTreeMap::new(),
-            });
-        let sender_allowance = sender_asset_balance
-            .allowance
-            .entry(caller.clone())
-            .or_insert(0);
-        if *sender_allowance < value {
-            return Err(ServiceError::LackOfBalance {
-                expect: value,
-                real:   *sender_allowance,
-            }
-            .into());
+            self.sdk
+                .set_value(index_elem_key(index, pos - 1), last_elem)?;
+            self.sdk.set_value(last_pos_key, pos)?;
         }
-        let after_sender_allowance = *sender_allowance - value;
-        sender_asset_balance
-            .allowance
-            .entry(caller.clone())
-            .and_modify(|e| *e = after_sender_allowance)
-            .or_insert(after_sender_allowance);
-        self.sdk
-            .set_account_value(&sender, asset_id.clone(), sender_asset_balance)?;
 
-        self._transfer(sender.clone(), recipient.clone(), asset_id.clone(), value)?;
+        self.sdk.set_value(pos_key, 0u64)?;
+        self.sdk.set_value(index_len_key(index), last)
+    }
 
-        let event = TransferFromEvent {
-            asset_id,
-            caller,
-            sender,
-            recipient,
+    // The hook runs through the riscv service's `call` and approves the transfer
+    // by returning `TRANSFER_HOOK_APPROVED`. Anything else, including the error
+    // text of a non-zero exit code, aborts it. Cycles used by the contract are
+    // charged to `ctx`.
+    //
+    // Besides `_transfer`, vestings and htlcs call it when they are created
+    // since their recipient is known by then, distributions call it on every
+    // claim.
+    fn _call_transfer_hook(
+        &self,
+        ctx: &ServiceContext,
+        sender: &Address,
+        recipient: &Address,
+        asset_id: &Hash,
+        value: u128,
+    ) -> ProtocolResult<()> {
+        let hook = match self.assets.get(asset_id)?.transfer_hook {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+
+        let args = TransferHookArgs {
+            asset_id: asset_id.clone(),
+            sender: sender.clone(),
+            recipient: recipient.clone(),
             value,
         };
-        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
-        ctx.emit_event(event_str)
+        let payload = TransferHookPayload {
+            address: hook.clone(),
+            args:    serde_json::to_string(&args).map_err(ServiceError::JsonParse)?,
+        };
+        let payload_str = serde_json::to_string(&payload).map_err(ServiceError::JsonParse)?;
+
+        let ret = self.sdk.read(ctx, None, "riscv", "call", &payload_str)?;
+        match serde_json::from_str::<String>(&ret) {
+            Ok(ref ret) if ret == TRANSFER_HOOK_APPROVED => Ok(()),
+            _ => Err(ServiceError::TransferRejected { hook }.into()),
+        }
     }
 
     fn _transfer(
         &mut self,
+        ctx: &ServiceContext,
         sender: Address,
         recipient: Address,
         asset_id: Hash,
-        value: u64,
+        value: u128,
     ) -> ProtocolResult<()> {
         if sender == recipient {
             return Err(ServiceError::RecipientIsSender.into());
         }
 
+        self._check_not_paused(&asset_id)?;
+        self._check_not_frozen(&asset_id, &sender)?;
+        self._check_not_frozen(&asset_id, &recipient)?;
+
+        // Balances are loaded after the hook so nothing it touches goes stale
+        self._call_transfer_hook(ctx, &sender, &recipient, &asset_id, value)?;
+
         let mut sender_asset_balance: AssetBalance = self
             .sdk
             .get_account_value(&sender, &asset_id)?
-            .unwrap_or(AssetBalance {
-                value:     0,
-                allowance: BTreeMap::new(),
-            });
+            .unwrap_or_default();
         let sender_balance = sender_asset_balance.value;
 
         if sender_balance < value {
@@ -317,32 +2726,291 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         let mut to_asset_balance: AssetBalance = self
             .sdk
             .get_account_value(&recipient, &asset_id)?
-            .unwrap_or(AssetBalance {
-                value:     0,
-                allowance: BTreeMap::new(),
-            });
+            .unwrap_or_default();
 
         let (v, overflow) = to_asset_balance.value.overflowing_add(value);
         if overflow {
-            return Err(ServiceError::U64Overflow.into());
+            return Err(ServiceError::U128Overflow.into());
         }
         to_asset_balance.value = v;
 
-        self.sdk
-            .set_account_value(&recipient, asset_id.clone(), to_asset_balance)?;
+        self._set_balance(
+            ctx.get_current_height(),
+            &recipient,
+            &asset_id,
+            to_asset_balance,
+        )?;
 
         let (v, overflow) = sender_balance.overflowing_sub(value);
         if overflow {
-            return Err(ServiceError::U64Overflow.into());
+            return Err(ServiceError::U128Overflow.into());
         }
         sender_asset_balance.value = v;
-        self.sdk
-            .set_account_value(&sender, asset_id, sender_asset_balance)?;
+        self._set_balance(
+            ctx.get_current_height(),
+            &sender,
+            &asset_id,
+            sender_asset_balance,
+        )?;
 
         Ok(())
     }
 }
 
+fn check_supply_policy(
+    supply: u128,
+    max_supply: Option<u128>,
+    issuance: &Option<IssuancePolicy>,
+) -> ProtocolResult<()> {
+    if let Some(max_supply) = max_supply {
+        if supply > max_supply {
+            return Err(ServiceError::ExceedMaxSupply { max_supply, supply }.into());
+        }
+    }
+    if let Some(policy) = issuance {
+        if policy.period == 0 {
+            return Err(ServiceError::InvalidIssuancePolicy.into());
+        }
+    }
+
+    Ok(())
+}
+
+fn charge_memo(ctx: &ServiceContext, memo: &Option<String>) -> ProtocolResult<()> {
+    if let Some(memo) = memo {
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(ServiceError::MemoTooLong {
+                len: memo.len(),
+                max: MAX_MEMO_LEN,
+            }
+            .into());
+        }
+
+        ctx.sub_cycles(memo.len() as u64 * MEMO_CYCLES_PER_BYTE)?;
+    }
+
+    Ok(())
+}
+
+fn vesting_status(vesting: Vesting, height: u64) -> VestingStatus {
+    let vested = vesting.vested(height);
+
+    VestingStatus {
+        locked: vesting.total - vested,
+        claimable: vested - vesting.claimed,
+        vesting,
+    }
+}
+
+// Computes `a * b / c` rounded down through a 256-bit product, returns none
+// if the result does not fit in 128 bits.
+fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+
+    let (hi, lo) = mul_wide(a, b);
+    if hi >= c {
+        return None;
+    }
+
+    // Shift-subtract long division, `rem` stays below `c`
+    let mut rem = hi;
+    let mut quo = 0u128;
+    for i in (0..128).rev() {
+        let carry = rem >> 127;
+        rem = (rem << 1) | ((lo >> i) & 1);
+        quo <<= 1;
+        if carry == 1 || rem >= c {
+            rem = rem.wrapping_sub(c);
+            quo |= 1;
+        }
+    }
+    Some(quo)
+}
+
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    let mask = u128::from(u64::max_value());
+    let (a_hi, a_lo) = (a >> 64, a & mask);
+    let (b_hi, b_lo) = (b >> 64, b & mask);
+
+    let lo_lo = a_lo * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_lo = a_hi * b_lo;
+    let hi_hi = a_hi * b_hi;
+
+    let mid = (lo_lo >> 64) + (lo_hi & mask) + (hi_lo & mask);
+    let lo = (lo_lo & mask) | (mid << 64);
+    let hi = hi_hi + (lo_hi >> 64) + (hi_lo >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+fn compose_key(parts: &[&[u8]]) -> Hash {
+    let mut key = BytesMut::new();
+    for part in parts {
+        key.extend_from_slice(part);
+    }
+    Hash::digest(key.freeze())
+}
+
+fn sha256(data: &[u8]) -> ProtocolResult<Hash> {
+    Hash::from_bytes(Bytes::copy_from_slice(&Sha256::digest(data)))
+}
+
+// Asset ids only depend on the issuer and how many assets it has created
+// before, so they can be predicted ahead of the creating transaction.
+fn asset_id(issuer: &Address, nonce: u64) -> Hash {
+    compose_key(&[issuer.as_bytes().as_ref(), &nonce.to_be_bytes()])
+}
+
+fn asset_nonce_key() -> Hash {
+    compose_key(&[ASSET_NONCE_KEY.as_bytes()])
+}
+
+fn allowance_key(asset_id: &Hash, grantee: &Address) -> Hash {
+    compose_key(&[
+        asset_id.as_bytes().as_ref(),
+        ALLOWANCE_KEY.as_bytes(),
+        grantee.as_bytes().as_ref(),
+    ])
+}
+
+fn permit_nonce_key() -> Hash {
+    compose_key(&[PERMIT_NONCE_KEY.as_bytes()])
+}
+
+fn frozen_key(asset_id: &Hash) -> Hash {
+    compose_key(&[asset_id.as_bytes().as_ref(), FROZEN_KEY.as_bytes()])
+}
+
+fn assets_index() -> Hash {
+    compose_key(&[ASSETS_INDEX_KEY.as_bytes()])
+}
+
+fn issuer_index(issuer: &Address) -> Hash {
+    compose_key(&[ISSUER_INDEX_KEY.as_bytes(), issuer.as_bytes().as_ref()])
+}
+
+fn symbol_index(symbol: &str) -> Hash {
+    compose_key(&[SYMBOL_INDEX_KEY.as_bytes(), symbol.as_bytes()])
+}
+
+fn holders_index(asset_id: &Hash) -> Hash {
+    compose_key(&[HOLDERS_INDEX_KEY.as_bytes(), asset_id.as_bytes().as_ref()])
+}
+
+fn vesting_key(id: u64) -> Hash {
+    compose_key(&[VESTING_KEY.as_bytes(), &id.to_be_bytes()])
+}
+
+fn vesting_count_key() -> Hash {
+    compose_key(&[VESTING_COUNT_KEY.as_bytes()])
+}
+
+fn beneficiary_vestings_index(beneficiary: &Address) -> Hash {
+    compose_key(&[
+        BENEFICIARY_VESTINGS_INDEX_KEY.as_bytes(),
+        beneficiary.as_bytes().as_ref(),
+    ])
+}
+
+fn htlc_key(id: u64) -> Hash {
+    compose_key(&[HTLC_KEY.as_bytes(), &id.to_be_bytes()])
+}
+
+fn htlc_count_key() -> Hash {
+    compose_key(&[HTLC_COUNT_KEY.as_bytes()])
+}
+
+fn distribution_key(id: u64) -> Hash {
+    compose_key(&[DISTRIBUTION_KEY.as_bytes(), &id.to_be_bytes()])
+}
+
+fn distribution_count_key() -> Hash {
+    compose_key(&[DISTRIBUTION_COUNT_KEY.as_bytes()])
+}
+
+fn distribution_claimed_key(id: u64) -> Hash {
+    compose_key(&[DISTRIBUTION_CLAIMED_KEY.as_bytes(), &id.to_be_bytes()])
+}
+
+// Prefixed so that collection ids never collide with asset ids
+fn collection_id(issuer: &Address, nonce: u64) -> Hash {
+    compose_key(&[
+        COLLECTION_NONCE_KEY.as_bytes(),
+        issuer.as_bytes().as_ref(),
+        &nonce.to_be_bytes(),
+    ])
+}
+
+fn collection_nonce_key() -> Hash {
+    compose_key(&[COLLECTION_NONCE_KEY.as_bytes()])
+}
+
+fn nft_key(collection_id: &Hash, token_id: u64) -> Hash {
+    compose_key(&[
+        NFT_KEY.as_bytes(),
+        collection_id.as_bytes().as_ref(),
+        &token_id.to_be_bytes(),
+    ])
+}
+
+fn nft_operator_key(collection_id: &Hash, operator: &Address) -> Hash {
+    compose_key(&[
+        NFT_OPERATOR_KEY.as_bytes(),
+        collection_id.as_bytes().as_ref(),
+        operator.as_bytes().as_ref(),
+    ])
+}
+
+fn owner_tokens_index(collection_id: &Hash, owner: &Address) -> Hash {
+    compose_key(&[
+        OWNER_TOKENS_INDEX_KEY.as_bytes(),
+        collection_id.as_bytes().as_ref(),
+        owner.as_bytes().as_ref(),
+    ])
+}
+
+fn issuance_key(asset_id: &Hash) -> Hash {
+    compose_key(&[ISSUANCE_KEY.as_bytes(), asset_id.as_bytes().as_ref()])
+}
+
+fn totals_key(asset_id: &Hash) -> Hash {
+    compose_key(&[TOTALS_KEY.as_bytes(), asset_id.as_bytes().as_ref()])
+}
+
+fn balance_checkpoints(asset_id: &Hash, user: &Address) -> Hash {
+    compose_key(&[
+        BALANCE_CHECKPOINTS_KEY.as_bytes(),
+        asset_id.as_bytes().as_ref(),
+        user.as_bytes().as_ref(),
+    ])
+}
+
+fn supply_checkpoints(asset_id: &Hash) -> Hash {
+    compose_key(&[
+        SUPPLY_CHECKPOINTS_KEY.as_bytes(),
+        asset_id.as_bytes().as_ref(),
+    ])
+}
+
+fn index_len_key(index: &Hash) -> Hash {
+    compose_key(&[index.as_bytes().as_ref(), b"len"])
+}
+
+fn index_elem_key(index: &Hash, pos: u64) -> Hash {
+    compose_key(&[index.as_bytes().as_ref(), b"elem", &pos.to_be_bytes()])
+}
+
+fn index_pos_key<T: FixedCodec>(index: &Hash, elem: &T) -> ProtocolResult<Hash> {
+    let elem = elem.encode_fixed()?;
+    Ok(compose_key(&[
+        index.as_bytes().as_ref(),
+        b"pos",
+        elem.as_ref(),
+    ]))
+}
+
 #[derive(Debug, Display, From)]
 pub enum ServiceError {
     #[display(fmt = "Parsing payload to json failed {:?}", _0)]
@@ -353,22 +3021,185 @@ pub enum ServiceError {
         id: Hash,
     },
 
+    #[display(
+        fmt = "Genesis allocations of asset {:?} sum to {}, expect supply {}",
+        id,
+        allocated,
+        supply
+    )]
+    GenesisAllocationMismatch {
+        id:        Hash,
+        supply:    u128,
+        allocated: u128,
+    },
+
     #[display(fmt = "Not found asset, id {:?}", id)]
     NotFoundAsset {
         id: Hash,
     },
 
-    #[display(fmt = "Not found asset, expect {:?} real {:?}", expect, real)]
-    LackOfBalance {
+    #[display(fmt = "Not found pending issuer of asset, id {:?}", id)]
+    NotFoundPendingIssuer {
+        id: Hash,
+    },
+
+    #[display(fmt = "Asset {:?} is paused", id)]
+    AssetPaused {
+        id: Hash,
+    },
+
+    #[display(fmt = "Account {:?} is frozen for asset {:?}", account, id)]
+    AccountFrozen {
+        id:      Hash,
+        account: Address,
+    },
+
+    #[display(fmt = "Allowance of asset {:?} expired at height {}", id, expiry)]
+    AllowanceExpired {
+        id:     Hash,
+        expiry: u64,
+    },
+
+    #[display(fmt = "Not found vesting, id {}", id)]
+    NotFoundVesting {
+        id: u64,
+    },
+
+    #[display(fmt = "Vesting needs a value, a duration and a cliff within the duration")]
+    InvalidVesting,
+
+    NothingToClaim,
+
+    #[display(fmt = "Supply {} exceeds max supply {}", supply, max_supply)]
+    ExceedMaxSupply {
+        max_supply: u128,
+        supply:     u128,
+    },
+
+    #[display(fmt = "Issued {} in this period exceeds limit {}", issued, limit)]
+    ExceedIssuanceLimit {
+        limit:  u128,
+        issued: u128,
+    },
+
+    #[display(fmt = "Full scan audit is only available in debug builds")]
+    FullScanDisabled,
+
+    #[display(fmt = "Issuance period must be positive")]
+    InvalidIssuancePolicy,
+
+    #[display(fmt = "Not found collection, id {:?}", id)]
+    NotFoundCollection {
+        id: Hash,
+    },
+
+    #[display(fmt = "Not found token {} of collection {:?}", token_id, collection_id)]
+    NotFoundNft {
+        collection_id: Hash,
+        token_id:      u64,
+    },
+
+    #[display(
+        fmt = "Token {} of collection {:?} already exists",
+        token_id,
+        collection_id
+    )]
+    NftExists {
+        collection_id: Hash,
+        token_id:      u64,
+    },
+
+    #[display(fmt = "Not found distribution, id {}", id)]
+    NotFoundDistribution {
+        id: u64,
+    },
+
+    #[display(
+        fmt = "Distribution needs a value, a past snapshot with a non-zero supply and a claim deadline not below the current height"
+    )]
+    InvalidDistribution,
+
+    #[display(fmt = "Distribution {} is closed for claims", id)]
+    DistributionClosed {
+        id: u64,
+    },
+
+    #[display(fmt = "Distribution {} is still open for claims", id)]
+    DistributionNotClosed {
+        id: u64,
+    },
+
+    #[display(fmt = "Not found htlc, id {}", id)]
+    NotFoundHtlc {
+        id: u64,
+    },
+
+    #[display(fmt = "Htlc needs a value and a timeout above the current height")]
+    InvalidHtlc,
+
+    #[display(fmt = "Htlc {} is already claimed or refunded", id)]
+    HtlcNotLocked {
+        id: u64,
+    },
+
+    #[display(fmt = "Htlc {} timed out", id)]
+    HtlcTimeout {
+        id: u64,
+    },
+
+    #[display(fmt = "Htlc {} has not timed out yet", id)]
+    HtlcNotTimeout {
+        id: u64,
+    },
+
+    InvalidPreimage,
+
+    #[display(fmt = "Permit expired at height {}", deadline)]
+    PermitExpired {
+        deadline: u64,
+    },
+
+    #[display(fmt = "Invalid permit nonce, expect {} real {}", expect, real)]
+    InvalidPermitNonce {
         expect: u64,
         real:   u64,
     },
 
-    U64Overflow,
+    #[display(fmt = "Permit is not signed by the grantor")]
+    InvalidPermitSignature,
+
+    #[display(fmt = "Transfer rejected by hook {:?}", hook)]
+    TransferRejected {
+        hook: Address,
+    },
+
+    #[display(fmt = "Hex decode error: {:?}", _0)]
+    HexDecode(hex::FromHexError),
+
+    #[display(fmt = "Inconsistent service state: {}", _0)]
+    InconsistentState(&'static str),
+
+    #[display(fmt = "Not found asset, expect {:?} real {:?}", expect, real)]
+    LackOfBalance {
+        expect: u128,
+        real:   u128,
+    },
+
+    #[display(fmt = "Memo is {} bytes, longer than {}", len, max)]
+    MemoTooLong {
+        len: usize,
+        max: usize,
+    },
+
+    U128Overflow,
+
+    NonAuthorized,
 
     RecipientIsSender,
 
     ApproveToYourself,
+
+    EmptyBatch,
 }
 
 impl std::error::Error for ServiceError {}
@@ -1,23 +1,119 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use cita_trie::MemoryDB;
+use common_crypto::{
+    Crypto, PrivateKey, PublicKey, Secp256k1, Secp256k1PrivateKey, Signature, ToPublicKey,
+};
 
 use framework::binding::sdk::{DefalutServiceSDK, DefaultChainQuerier};
 use framework::binding::state::{GeneralServiceState, MPTTrie};
-use protocol::traits::{NoopDispatcher, Storage};
+use protocol::fixed_codec::FixedCodec;
+use protocol::traits::{Dispatcher, ExecResp, NoopDispatcher, ServiceSDK, Storage};
 use protocol::types::{
-    Address, Block, Hash, Proof, Receipt, ServiceContext, ServiceContextParams, SignedTransaction,
+    Address, Block, Hash, Metadata, Proof, Receipt, ServiceContext, ServiceContextParams,
+    SignedTransaction,
 };
 use protocol::{types::Bytes, ProtocolResult};
 
 use crate::types::{
-    ApprovePayload, CreateAssetPayload, GetAllowancePayload, GetAssetPayload, GetBalancePayload,
-    TransferFromPayload, TransferPayload,
+    AcceptIssuerPayload, ApproveEvent, ApproveNftPayload, ApprovePayload, Asset, AssetBalance,
+    AuditAssetPayload, BatchTransferItem, BatchTransferPayload, BurnPayload, CancelIssuerPayload,
+    ClaimDistributionPayload, ClaimHtlcPayload, ClaimVestedPayload, CreateAssetPayload,
+    CreateCollectionPayload, CreateVestingPayload, DecreaseAllowancePayload, DistributePayload,
+    FreezeAccountPayload, GenesisAllocation, GenesisAsset, GetAllowancePayload,
+    GetAllowancesPayload, GetAssetPayload, GetBalanceAtPayload, GetBalancePayload,
+    GetBalancesPayload, GetDistributionPayload, GetDistributionSharePayload, GetFrozenPayload,
+    GetHoldersPayload, GetHtlcPayload, GetNftPayload, GetOperatorPayload, GetPausedPayload,
+    GetPermitNoncePayload, GetSupplyAtPayload, GetVestingPayload, GetVestingsPayload, Holder,
+    HtlcState, IncreaseAllowancePayload, IndexAssetPayload, InitGenesisPayload, IssuancePolicy,
+    ListAssetsPayload, LockHtlcPayload, MintNftPayload, MintPayload, OwnerOfPayload,
+    PauseAssetPayload, PermitMessage, PermitPayload, PredictAssetIdPayload, ProposeIssuerPayload,
+    ReclaimDistributionPayload, RefundHtlcPayload, SetOperatorPayload, SetTransferHookPayload,
+    TokensOfPayload, TransferEvent, TransferFromPayload, TransferHookArgs, TransferHookPayload,
+    TransferNftEvent, TransferNftPayload, TransferPayload, UnfreezeAccountPayload,
+    UnpauseAssetPayload, UpdateAssetInfoPayload,
+};
+use crate::{
+    index_len_key, mul_div, sha256, AssetService, BATCH_LEG_CYCLES, PERMIT_DOMAIN,
+    TRANSFER_HOOK_APPROVED,
 };
-use crate::AssetService;
+
+#[test]
+fn test_init_genesis() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let id_a = Hash::digest(Bytes::from("a"));
+    let id_b = Hash::digest(Bytes::from("b"));
+    service
+        .init_genesis(InitGenesisPayload {
+            assets: vec![
+                mock_genesis_asset(id_a.clone(), issuer.clone(), 1000, vec![
+                    GenesisAllocation {
+                        address: issuer.clone(),
+                        amount:  600,
+                    },
+                    GenesisAllocation {
+                        address: user.clone(),
+                        amount:  400,
+                    },
+                ]),
+                mock_genesis_asset(id_b.clone(), user.clone(), 500, vec![GenesisAllocation {
+                    address: user.clone(),
+                    amount:  500,
+                }]),
+            ],
+        })
+        .unwrap();
+
+    let expect_balances = vec![
+        (id_a.clone(), issuer.clone(), 600),
+        (id_a, user.clone(), 400),
+        (id_b.clone(), issuer, 0),
+        (id_b.clone(), user.clone(), 500),
+    ];
+    for (asset_id, user, balance) in expect_balances {
+        let balance_res = service
+            .get_balance(context.clone(), GetBalancePayload { asset_id, user })
+            .unwrap();
+        assert_eq!(balance_res.balance, balance);
+    }
+
+    let asset = service
+        .get_asset(context, GetAssetPayload { id: id_b })
+        .unwrap();
+    assert_eq!(asset.issuer, user);
+    assert_eq!(asset.supply, 500);
+}
+
+#[test]
+fn test_init_genesis_allocation_mismatch() {
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let genesis_res = service.init_genesis(InitGenesisPayload {
+        assets: vec![mock_genesis_asset(
+            Hash::digest(Bytes::from("a")),
+            issuer.clone(),
+            1000,
+            vec![GenesisAllocation {
+                address: issuer,
+                amount:  999,
+            }],
+        )],
+    });
+    assert!(genesis_res.is_err());
+}
 
 #[test]
 fn test_create_asset() {
@@ -28,12 +124,20 @@ fn test_create_asset() {
     let mut service = new_asset_service();
 
     let supply = 1024 * 1024;
+    let predicted = service
+        .predict_asset_id(context.clone(), PredictAssetIdPayload {
+            issuer: caller.clone(),
+        })
+        .unwrap();
+    assert_eq!(predicted.nonce, 0);
+
     // test create_asset
     let asset = service
         .create_asset(context.clone(), CreateAssetPayload {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            ..Default::default()
         })
         .unwrap();
 
@@ -45,13 +149,241 @@ fn test_create_asset() {
     assert_eq!(asset, new_asset);
 
     let balance_res = service
-        .get_balance(context, GetBalancePayload {
+        .get_balance(context.clone(), GetBalancePayload {
             asset_id: asset.id.clone(),
-            user:     caller,
+            user:     caller.clone(),
         })
         .unwrap();
     assert_eq!(balance_res.balance, supply);
     assert_eq!(balance_res.asset_id, asset.id);
+    assert_eq!(predicted.asset_id, asset.id);
+
+    // the same payload yields a new asset with a different id
+    let predicted = service
+        .predict_asset_id(context.clone(), PredictAssetIdPayload { issuer: caller })
+        .unwrap();
+    assert_eq!(predicted.nonce, 1);
+
+    let same_asset = service
+        .create_asset(context, CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_ne!(same_asset.id, asset.id);
+    assert_eq!(same_asset.id, predicted.asset_id);
+}
+
+#[test]
+fn test_list_assets() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let other = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let other_context = mock_context(cycles_limit, other.clone());
+
+    let mut service = new_asset_service();
+
+    let asset_a = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "a".to_owned(),
+            symbol: "AAA".to_owned(),
+            supply: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+    let asset_b = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "b".to_owned(),
+            symbol: "BBB".to_owned(),
+            supply: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+    let asset_c = service
+        .create_asset(other_context, CreateAssetPayload {
+            name: "c".to_owned(),
+            symbol: "AAA".to_owned(),
+            supply: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let page = service
+        .list_assets(context.clone(), ListAssetsPayload {
+            cursor: 0,
+            limit:  2,
+            issuer: None,
+            symbol: None,
+        })
+        .unwrap();
+    assert_eq!(page.assets, vec![asset_a.clone(), asset_b.clone()]);
+    assert_eq!(page.next_cursor, Some(2));
+
+    let page = service
+        .list_assets(context.clone(), ListAssetsPayload {
+            cursor: 2,
+            limit:  2,
+            issuer: None,
+            symbol: None,
+        })
+        .unwrap();
+    assert_eq!(page.assets, vec![asset_c.clone()]);
+    assert_eq!(page.next_cursor, None);
+
+    let page = service
+        .list_assets(context.clone(), ListAssetsPayload {
+            cursor: 0,
+            limit:  10,
+            issuer: Some(caller),
+            symbol: None,
+        })
+        .unwrap();
+    assert_eq!(page.assets, vec![asset_a.clone(), asset_b]);
+
+    let page = service
+        .list_assets(context.clone(), ListAssetsPayload {
+            cursor: 0,
+            limit:  10,
+            issuer: None,
+            symbol: Some("AAA".to_owned()),
+        })
+        .unwrap();
+    assert_eq!(page.assets, vec![asset_a, asset_c.clone()]);
+
+    let page = service
+        .list_assets(context, ListAssetsPayload {
+            cursor: 0,
+            limit:  10,
+            issuer: Some(other),
+            symbol: Some("AAA".to_owned()),
+        })
+        .unwrap();
+    assert_eq!(page.assets, vec![asset_c]);
+}
+
+#[test]
+fn test_update_asset_info() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            decimals: 8,
+            description: "test asset".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(asset.decimals, 8);
+    assert_eq!(asset.description, "test asset");
+
+    service
+        .update_asset_info(context.clone(), UpdateAssetInfoPayload {
+            asset_id:    asset.id.clone(),
+            description: "updated".to_owned(),
+            logo_uri:    "https://example.com/logo.png".to_owned(),
+            website:     "https://example.com".to_owned(),
+        })
+        .unwrap();
+
+    let new_asset = service
+        .get_asset(context, GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(new_asset.decimals, 8);
+    assert_eq!(new_asset.description, "updated");
+    assert_eq!(new_asset.logo_uri, "https://example.com/logo.png");
+    assert_eq!(new_asset.website, "https://example.com");
+
+    // only issuer can update
+    let other = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let update_res =
+        service.update_asset_info(mock_context(cycles_limit, other), UpdateAssetInfoPayload {
+            asset_id:    asset.id,
+            description: String::new(),
+            logo_uri:    String::new(),
+            website:     String::new(),
+        });
+    assert!(update_res.is_err());
+}
+
+#[test]
+fn test_decode_legacy_asset() {
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let id = Hash::digest(Bytes::from("legacy"));
+
+    let mut stream = rlp::RlpStream::new_list(5);
+    stream
+        .append(&id)
+        .append(&"test".to_owned())
+        .append(&"TT".to_owned())
+        .append(&1024u64)
+        .append(&issuer);
+    let legacy = Bytes::from(stream.out());
+
+    let asset = Asset::decode_fixed(legacy).unwrap();
+    assert_eq!(asset.id, id);
+    assert_eq!(asset.supply, 1024);
+    assert_eq!(asset.issuer, issuer);
+    assert_eq!(asset.decimals, 0);
+    assert_eq!(asset.description, "");
+
+    let asset_again = Asset::decode_fixed(asset.encode_fixed().unwrap()).unwrap();
+    assert_eq!(asset, asset_again);
+}
+
+#[test]
+fn test_decode_legacy_balance() {
+    let grantee = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut allowance = rlp::RlpStream::new_list(2);
+    allowance.append(&grantee).append(&24u64);
+    let mut stream = rlp::RlpStream::new_list(2);
+    stream.append(&1024u64);
+    stream.begin_list(1).append_raw(&allowance.out(), 1);
+    let legacy = Bytes::from(stream.out());
+
+    let asset_balance = AssetBalance::decode_fixed(legacy.clone()).unwrap();
+    assert_eq!(asset_balance.value, 1024);
+    assert_eq!(asset_balance.legacy_allowance.get(&grantee), Some(&24));
+
+    // u64 values are encoded the same way after widening
+    assert_eq!(asset_balance.encode_fixed().unwrap(), legacy);
+}
+
+#[test]
+fn test_amount_json() {
+    let payload: TransferPayload = serde_json::from_str(
+        r#"{
+            "asset_id": "f56924db538e77bb5951eb5ff0d02b88983c49c45eea30e8ae3e7234b311436c",
+            "to": "f8389d774afdad8755ef8e629e5a154fddc6325a",
+            "value": "340282366920938463463374607431768211455"
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(payload.value, u128::max_value());
+
+    let payload: TransferPayload = serde_json::from_str(
+        r#"{
+            "asset_id": "f56924db538e77bb5951eb5ff0d02b88983c49c45eea30e8ae3e7234b311436c",
+            "to": "f8389d774afdad8755ef8e629e5a154fddc6325a",
+            "value": 1024
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(payload.value, 1024);
+
+    let json = serde_json::to_value(&payload).unwrap();
+    assert_eq!(json["value"], "1024");
 }
 
 #[test]
@@ -69,6 +401,7 @@ fn test_transfer() {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            ..Default::default()
         })
         .unwrap();
 
@@ -78,6 +411,7 @@ fn test_transfer() {
             asset_id: asset.id.clone(),
             to:       to_address.clone(),
             value:    1024,
+            memo:     None,
         })
         .unwrap();
 
@@ -100,10 +434,65 @@ fn test_transfer() {
 }
 
 #[test]
-fn test_approve() {
+fn test_transfer_memo() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(
+            mock_context(cycles_limit, caller.clone()),
+            CreateAssetPayload {
+                name: "test".to_owned(),
+                symbol: "test".to_owned(),
+                supply: 1024 * 1024,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let context = mock_context(cycles_limit, caller.clone());
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    24,
+            memo:     Some("deposit-1024".to_owned()),
+        })
+        .unwrap();
+
+    let events = context.get_events();
+    let event: TransferEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.memo, Some("deposit-1024".to_owned()));
+
+    let no_memo_context = mock_context(cycles_limit, caller.clone());
+    service
+        .transfer(no_memo_context.clone(), TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    24,
+            memo:     None,
+        })
+        .unwrap();
+    assert!(context.get_cycles_used() > no_memo_context.get_cycles_used());
+
+    let transfer_res = service.transfer(mock_context(cycles_limit, caller), TransferPayload {
+        asset_id: asset.id,
+        to:       to_address,
+        value:    24,
+        memo:     Some("m".repeat(257)),
+    });
+    assert!(transfer_res.is_err());
+}
+
+#[test]
+fn test_balance_checkpoints() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
     let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
 
     let mut service = new_asset_service();
 
@@ -113,32 +502,139 @@ fn test_approve() {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            ..Default::default()
         })
         .unwrap();
 
-    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    for height in &[5, 5, 10] {
+        service
+            .transfer(
+                mock_context_at(cycles_limit, caller.clone(), *height),
+                TransferPayload {
+                    asset_id: asset.id.clone(),
+                    to:       to_address.clone(),
+                    value:    100,
+                    memo:     None,
+                },
+            )
+            .unwrap();
+    }
     service
-        .approve(context.clone(), ApprovePayload {
-            asset_id: asset.id.clone(),
-            to:       to_address.clone(),
-            value:    1024,
+        .mint(
+            mock_context_at(cycles_limit, caller.clone(), 12),
+            MintPayload {
+                asset_id: asset.id.clone(),
+                to:       to_address.clone(),
+                value:    1000,
+            },
+        )
+        .unwrap();
+
+    let expect_balances = vec![
+        (0, 0, 0),
+        (1, supply, 0),
+        (4, supply, 0),
+        (5, supply - 200, 200),
+        (9, supply - 200, 200),
+        (10, supply - 300, 300),
+        (12, supply - 300, 1300),
+        (100, supply - 300, 1300),
+    ];
+    for (height, caller_balance, to_balance) in expect_balances {
+        let balance_res = service
+            .get_balance_at(context.clone(), GetBalanceAtPayload {
+                asset_id: asset.id.clone(),
+                user: caller.clone(),
+                height,
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, caller_balance);
+
+        let balance_res = service
+            .get_balance_at(context.clone(), GetBalanceAtPayload {
+                asset_id: asset.id.clone(),
+                user: to_address.clone(),
+                height,
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, to_balance);
+    }
+
+    let expect_supplies = vec![(0, 0), (1, supply), (11, supply), (12, supply + 1000)];
+    for (height, expect_supply) in expect_supplies {
+        let supply_res = service
+            .get_supply_at(context.clone(), GetSupplyAtPayload {
+                asset_id: asset.id.clone(),
+                height,
+            })
+            .unwrap();
+        assert_eq!(supply_res.supply, expect_supply);
+    }
+}
+
+#[test]
+fn test_balance_checkpoints_before_upgrade() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let holder = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
         })
         .unwrap();
 
-    let allowance_res = service
-        .get_allowance(context, GetAllowancePayload {
+    // balance written before checkpoints were recorded
+    service
+        .sdk
+        .set_account_value(&holder, asset.id.clone(), AssetBalance {
+            value: 500,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let balance_res = service
+        .get_balance_at(context.clone(), GetBalanceAtPayload {
             asset_id: asset.id.clone(),
-            grantor:  caller,
-            grantee:  to_address.clone(),
+            user:     holder.clone(),
+            height:   3,
         })
         .unwrap();
-    assert_eq!(allowance_res.asset_id, asset.id);
-    assert_eq!(allowance_res.grantee, to_address);
-    assert_eq!(allowance_res.value, 1024);
+    assert_eq!(balance_res.balance, 500);
+
+    service
+        .transfer(
+            mock_context_at(cycles_limit, holder.clone(), 20),
+            TransferPayload {
+                asset_id: asset.id.clone(),
+                to:       caller,
+                value:    100,
+                memo:     None,
+            },
+        )
+        .unwrap();
+
+    let expect_balances = vec![(3, 500), (20, 400)];
+    for (height, balance) in expect_balances {
+        let balance_res = service
+            .get_balance_at(context.clone(), GetBalanceAtPayload {
+                asset_id: asset.id.clone(),
+                user: holder.clone(),
+                height,
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, balance);
+    }
 }
 
 #[test]
-fn test_transfer_from() {
+fn test_batch_transfer() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
     let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     let context = mock_context(cycles_limit, caller.clone());
@@ -151,78 +647,2156 @@ fn test_transfer_from() {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            ..Default::default()
         })
         .unwrap();
 
-    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let to_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let to_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let one_context = mock_context(cycles_limit, caller.clone());
     service
-        .approve(context.clone(), ApprovePayload {
-            asset_id: asset.id.clone(),
-            to:       to_address.clone(),
-            value:    1024,
+        .batch_transfer(one_context.clone(), BatchTransferPayload {
+            asset_id:  asset.id.clone(),
+            transfers: vec![BatchTransferItem {
+                to:    to_a.clone(),
+                value: 0,
+            }],
         })
         .unwrap();
-
-    let to_context = mock_context(cycles_limit, to_address.clone());
-
+    let two_context = mock_context(cycles_limit, caller.clone());
     service
-        .transfer_from(to_context.clone(), TransferFromPayload {
+        .batch_transfer(two_context.clone(), BatchTransferPayload {
             asset_id:  asset.id.clone(),
-            sender:    caller.clone(),
-            recipient: to_address.clone(),
-            value:     24,
+            transfers: vec![
+                BatchTransferItem {
+                    to:    to_a.clone(),
+                    value: 1024,
+                },
+                BatchTransferItem {
+                    to:    to_b.clone(),
+                    value: 2048,
+                },
+            ],
         })
         .unwrap();
 
-    let allowance_res = service
-        .get_allowance(context.clone(), GetAllowancePayload {
+    // the base cost covers the first leg
+    assert_eq!(
+        two_context.get_cycles_used(),
+        one_context.get_cycles_used() + BATCH_LEG_CYCLES
+    );
+
+    let balance_res = service
+        .get_balance(context.clone(), GetBalancePayload {
             asset_id: asset.id.clone(),
-            grantor:  caller.clone(),
-            grantee:  to_address.clone(),
+            user:     caller,
         })
         .unwrap();
-    assert_eq!(allowance_res.asset_id, asset.id.clone());
-    assert_eq!(allowance_res.grantee, to_address.clone());
-    assert_eq!(allowance_res.value, 1000);
+    assert_eq!(balance_res.balance, supply - 1024 - 2048);
 
     let balance_res = service
-        .get_balance(context, GetBalancePayload {
+        .get_balance(context.clone(), GetBalancePayload {
             asset_id: asset.id.clone(),
-            user:     caller,
+            user:     to_a.clone(),
         })
         .unwrap();
-    assert_eq!(balance_res.balance, supply - 24);
+    assert_eq!(balance_res.balance, 1024);
 
     let balance_res = service
-        .get_balance(to_context, GetBalancePayload {
-            asset_id: asset.id,
-            user:     to_address,
+        .get_balance(context.clone(), GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     to_b,
         })
         .unwrap();
-    assert_eq!(balance_res.balance, 24);
+    assert_eq!(balance_res.balance, 2048);
+
+    let batch_res = service.batch_transfer(context, BatchTransferPayload {
+        asset_id:  asset.id,
+        transfers: vec![BatchTransferItem {
+            to:    to_a,
+            value: supply,
+        }],
+    });
+    assert!(batch_res.is_err());
 }
 
-fn new_asset_service() -> AssetService<
-    DefalutServiceSDK<
-        GeneralServiceState<MemoryDB>,
-        DefaultChainQuerier<MockStorage>,
-        NoopDispatcher,
-    >,
-> {
-    let chain_db = DefaultChainQuerier::new(Arc::new(MockStorage {}));
-    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
-    let state = GeneralServiceState::new(trie);
+#[test]
+fn test_get_holders() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
 
-    let sdk = DefalutServiceSDK::new(
-        Rc::new(RefCell::new(state)),
-        Rc::new(chain_db),
-        NoopDispatcher {},
-    );
+    let mut service = new_asset_service();
 
-    AssetService::new(sdk).unwrap()
-}
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
 
-fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
+    let to_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let to_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .batch_transfer(context.clone(), BatchTransferPayload {
+            asset_id:  asset.id.clone(),
+            transfers: vec![
+                BatchTransferItem {
+                    to:    to_a.clone(),
+                    value: 1024,
+                },
+                BatchTransferItem {
+                    to:    to_b.clone(),
+                    value: 2048,
+                },
+            ],
+        })
+        .unwrap();
+
+    let holders_res = service
+        .get_holders(context.clone(), GetHoldersPayload {
+            asset_id: asset.id.clone(),
+            cursor:   0,
+            limit:    2,
+        })
+        .unwrap();
+    assert_eq!(holders_res.holder_count, 3);
+    assert_eq!(holders_res.holders, vec![
+        Holder {
+            user:    caller.clone(),
+            balance: supply - 1024 - 2048,
+        },
+        Holder {
+            user:    to_a.clone(),
+            balance: 1024,
+        },
+    ]);
+    assert_eq!(holders_res.next_cursor, Some(2));
+
+    // a holder that moves out its whole balance is dropped
+    service
+        .transfer(mock_context(cycles_limit, to_a), TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       caller.clone(),
+            value:    1024,
+            memo:     None,
+        })
+        .unwrap();
+
+    let holders_res = service
+        .get_holders(context, GetHoldersPayload {
+            asset_id: asset.id,
+            cursor:   0,
+            limit:    10,
+        })
+        .unwrap();
+    assert_eq!(holders_res.holder_count, 2);
+    assert_eq!(holders_res.holders, vec![
+        Holder {
+            user:    caller,
+            balance: supply - 2048,
+        },
+        Holder {
+            user:    to_b,
+            balance: 2048,
+        },
+    ]);
+    assert_eq!(holders_res.next_cursor, None);
+}
+
+#[test]
+fn test_index_asset() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let holder = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let stranger = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    // asset and balance written before the indexes existed
+    let asset = Asset {
+        id:            Hash::digest(Bytes::from("legacy")),
+        name:          "legacy".to_owned(),
+        symbol:        "LGC".to_owned(),
+        supply:        1024,
+        issuer:        caller,
+        decimals:      0,
+        description:   String::new(),
+        logo_uri:      String::new(),
+        website:       String::new(),
+        max_supply:    None,
+        issuance:      None,
+        transfer_hook: None,
+    };
+    service
+        .assets
+        .insert(asset.id.clone(), asset.clone())
+        .unwrap();
+    service
+        .sdk
+        .set_account_value(&holder, asset.id.clone(), AssetBalance {
+            value: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let list_payload = ListAssetsPayload {
+        cursor: 0,
+        limit:  10,
+        issuer: None,
+        symbol: None,
+    };
+    let holders_payload = GetHoldersPayload {
+        asset_id: asset.id.clone(),
+        cursor:   0,
+        limit:    10,
+    };
+    let page = service
+        .list_assets(context.clone(), list_payload.clone())
+        .unwrap();
+    assert!(page.assets.is_empty());
+    let holders_res = service
+        .get_holders(context.clone(), holders_payload.clone())
+        .unwrap();
+    assert_eq!(holders_res.holder_count, 0);
+
+    // accounts without a balance are skipped, indexing twice changes nothing
+    for _ in 0..2 {
+        service
+            .index_asset(context.clone(), IndexAssetPayload {
+                asset_id: asset.id.clone(),
+                holders:  vec![holder.clone(), stranger.clone()],
+            })
+            .unwrap();
+    }
+
+    let page = service.list_assets(context.clone(), list_payload).unwrap();
+    assert_eq!(page.assets, vec![asset]);
+    let holders_res = service
+        .get_holders(context.clone(), holders_payload)
+        .unwrap();
+    assert_eq!(holders_res.holder_count, 1);
+    assert_eq!(holders_res.holders, vec![Holder {
+        user:    holder,
+        balance: 1024,
+    }]);
+
+    let index_res = service.index_asset(context, IndexAssetPayload {
+        asset_id: Hash::digest(Bytes::from("unknown")),
+        holders:  vec![],
+    });
+    assert!(index_res.is_err());
+}
+
+#[test]
+fn test_index_remove_inconsistent() {
+    let mut service = new_asset_service();
+    let index = Hash::digest(Bytes::from("index"));
+    let elem = Hash::digest(Bytes::from("elem"));
+
+    service._index_insert(&index, elem.clone()).unwrap();
+    // the element keeps its position while the length is lost
+    service.sdk.set_value(index_len_key(&index), 0u64).unwrap();
+
+    let remove_res = service._index_remove(&index, &elem);
+    assert!(remove_res.is_err());
+}
+
+#[test]
+fn test_batch_queries() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let mut assets = Vec::new();
+    for supply in [1000, 2000].iter() {
+        let asset = service
+            .create_asset(context.clone(), CreateAssetPayload {
+                name: "test".to_owned(),
+                symbol: "test".to_owned(),
+                supply: *supply,
+                ..Default::default()
+            })
+            .unwrap();
+        service
+            .transfer(context.clone(), TransferPayload {
+                asset_id: asset.id.clone(),
+                to:       user.clone(),
+                value:    supply / 10,
+                memo:     None,
+            })
+            .unwrap();
+        assets.push(asset);
+    }
+
+    // one user, many assets
+    let payload_str = format!(
+        r#"{{"user": "{}", "asset_ids": ["{}", "{}"]}}"#,
+        user.as_hex(),
+        assets[0].id.as_hex(),
+        assets[1].id.as_hex()
+    );
+    let payload: GetBalancesPayload = serde_json::from_str(&payload_str).unwrap();
+    let balances = service.get_balances(context.clone(), payload).unwrap();
+    let values: Vec<u128> = balances.iter().map(|b| b.balance).collect();
+    assert_eq!(values, vec![100, 200]);
+
+    // one asset, many users
+    let payload_str = format!(
+        r#"{{"asset_id": "{}", "users": ["{}", "{}"]}}"#,
+        assets[1].id.as_hex(),
+        caller.as_hex(),
+        user.as_hex()
+    );
+    let payload: GetBalancesPayload = serde_json::from_str(&payload_str).unwrap();
+    let balances = service.get_balances(context.clone(), payload).unwrap();
+    let values: Vec<u128> = balances.iter().map(|b| b.balance).collect();
+    assert_eq!(values, vec![1800, 200]);
+
+    // cycles grow with the number of items
+    let one_context = mock_context(cycles_limit, caller.clone());
+    service
+        .get_balances(one_context.clone(), GetBalancesPayload::ByUser {
+            user:      user.clone(),
+            asset_ids: vec![assets[0].id.clone()],
+        })
+        .unwrap();
+    let two_context = mock_context(cycles_limit, caller.clone());
+    service
+        .get_balances(two_context.clone(), GetBalancesPayload::ByUser {
+            user:      user.clone(),
+            asset_ids: vec![assets[0].id.clone(), assets[1].id.clone()],
+        })
+        .unwrap();
+    assert!(two_context.get_cycles_used() > one_context.get_cycles_used());
+
+    let unknown_res = service.get_balances(context.clone(), GetBalancesPayload::ByAsset {
+        asset_id: Hash::digest(Bytes::from("unknown")),
+        users:    vec![user.clone()],
+    });
+    assert!(unknown_res.is_err());
+
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: assets[0].id.clone(),
+            to:       user.clone(),
+            value:    10,
+            expiry:   None,
+        })
+        .unwrap();
+
+    let allowances = service
+        .get_allowances(context, GetAllowancesPayload {
+            allowances: assets
+                .iter()
+                .map(|asset| GetAllowancePayload {
+                    asset_id: asset.id.clone(),
+                    grantor:  caller.clone(),
+                    grantee:  user.clone(),
+                })
+                .collect(),
+        })
+        .unwrap();
+    let values: Vec<u128> = allowances.iter().map(|a| a.value).collect();
+    assert_eq!(values, vec![10, 0]);
+}
+
+#[test]
+fn test_approve() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+            expiry:   None,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context, GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller,
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.asset_id, asset.id);
+    assert_eq!(allowance_res.grantee, to_address);
+    assert_eq!(allowance_res.value, 1024);
+}
+
+#[test]
+fn test_transfer_from() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+            expiry:   None,
+        })
+        .unwrap();
+
+    let to_context = mock_context(cycles_limit, to_address.clone());
+
+    service
+        .transfer_from(to_context.clone(), TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller.clone(),
+            recipient: to_address.clone(),
+            value:     24,
+            memo:      None,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller.clone(),
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.asset_id, asset.id.clone());
+    assert_eq!(allowance_res.grantee, to_address.clone());
+    assert_eq!(allowance_res.value, 1000);
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, supply - 24);
+
+    let balance_res = service
+        .get_balance(to_context, GetBalancePayload {
+            asset_id: asset.id,
+            user:     to_address,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 24);
+}
+
+#[test]
+fn test_approve_paused_or_frozen() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let approve_payload = ApprovePayload {
+        asset_id: asset.id.clone(),
+        to:       to_address.clone(),
+        value:    24,
+        expiry:   None,
+    };
+
+    service
+        .pause_asset(context.clone(), PauseAssetPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+    let approve_res = service.approve(context.clone(), approve_payload.clone());
+    assert!(approve_res.is_err());
+    service
+        .unpause_asset(context.clone(), UnpauseAssetPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+
+    // frozen grantee
+    service
+        .freeze_account(context.clone(), FreezeAccountPayload {
+            asset_id: asset.id.clone(),
+            account:  to_address.clone(),
+        })
+        .unwrap();
+    let approve_res = service.approve(context.clone(), approve_payload.clone());
+    assert!(approve_res.is_err());
+    service
+        .unfreeze_account(context.clone(), UnfreezeAccountPayload {
+            asset_id: asset.id.clone(),
+            account:  to_address,
+        })
+        .unwrap();
+
+    // frozen grantor
+    service
+        .freeze_account(context.clone(), FreezeAccountPayload {
+            asset_id: asset.id.clone(),
+            account:  caller.clone(),
+        })
+        .unwrap();
+    let approve_res = service.approve(context.clone(), approve_payload.clone());
+    assert!(approve_res.is_err());
+    service
+        .unfreeze_account(context.clone(), UnfreezeAccountPayload {
+            asset_id: asset.id,
+            account:  caller.clone(),
+        })
+        .unwrap();
+
+    service.approve(context.clone(), approve_payload).unwrap();
+
+    let events = context.get_events();
+    let event: ApproveEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.grantor, caller);
+    assert_eq!(event.value, 24);
+}
+
+#[test]
+fn test_increase_decrease_allowance() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    100,
+            expiry:   Some(10),
+        })
+        .unwrap();
+    service
+        .increase_allowance(context.clone(), IncreaseAllowancePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    50,
+        })
+        .unwrap();
+    service
+        .decrease_allowance(context.clone(), DecreaseAllowancePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    30,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller,
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.value, 120);
+    assert_eq!(allowance_res.expiry, Some(10));
+
+    let decrease_res = service.decrease_allowance(context, DecreaseAllowancePayload {
+        asset_id: asset.id,
+        to:       to_address,
+        value:    121,
+    });
+    assert!(decrease_res.is_err());
+}
+
+#[test]
+fn test_allowance_expiry() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    service
+        .approve(context, ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    100,
+            expiry:   Some(10),
+        })
+        .unwrap();
+
+    // the allowance can still be spent at the expiry height
+    service
+        .transfer_from(
+            mock_context_at(cycles_limit, to_address.clone(), 10),
+            TransferFromPayload {
+                asset_id:  asset.id.clone(),
+                sender:    caller.clone(),
+                recipient: to_address.clone(),
+                value:     10,
+                memo:      None,
+            },
+        )
+        .unwrap();
+
+    let transfer_res = service.transfer_from(
+        mock_context_at(cycles_limit, to_address.clone(), 11),
+        TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller.clone(),
+            recipient: to_address.clone(),
+            value:     10,
+            memo:      None,
+        },
+    );
+    assert!(transfer_res.is_err());
+
+    // an expired allowance is renewed by approve, not increased
+    let increase_res = service.increase_allowance(
+        mock_context_at(cycles_limit, caller, 11),
+        IncreaseAllowancePayload {
+            asset_id: asset.id,
+            to:       to_address,
+            value:    10,
+        },
+    );
+    assert!(increase_res.is_err());
+}
+
+#[test]
+fn test_permit() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let priv_key = Secp256k1PrivateKey::try_from(
+        hex::decode("45c56be699dca666191ad3446897e0f480da234da896270202514a0e1a587c3f")
+            .unwrap()
+            .as_ref(),
+    )
+    .unwrap();
+    let pubkey = priv_key.pub_key().to_bytes();
+    let grantor = Address::from_pubkey_bytes(pubkey.clone()).unwrap();
+    let context = mock_context(cycles_limit, grantor.clone());
+    let grantee = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let relayer = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let relayer_context = mock_context(cycles_limit, relayer);
+
+    let mut service = new_asset_service_with(MetadataDispatcher);
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let message = PermitMessage {
+        domain:   PERMIT_DOMAIN.to_owned(),
+        chain_id: mock_chain_id(),
+        asset_id: asset.id.clone(),
+        grantor:  grantor.clone(),
+        grantee:  grantee.clone(),
+        value:    1024,
+        expiry:   None,
+        nonce:    0,
+        deadline: 10,
+    };
+    let sign = |message: &PermitMessage| {
+        let digest = Hash::digest(message.encode_fixed().unwrap());
+        let signature =
+            Secp256k1::sign_message(digest.as_bytes().as_ref(), &priv_key.to_bytes()).unwrap();
+        hex::encode(signature.to_bytes())
+    };
+
+    // permits signed for another chain are rejected
+    let permit_res = service.permit(relayer_context.clone(), PermitPayload {
+        asset_id:  asset.id.clone(),
+        grantor:   grantor.clone(),
+        grantee:   grantee.clone(),
+        value:     1024,
+        expiry:    None,
+        nonce:     0,
+        deadline:  10,
+        pubkey:    hex::encode(pubkey.clone()),
+        signature: sign(&PermitMessage {
+            chain_id: Hash::digest(Bytes::from("other")),
+            ..message.clone()
+        }),
+    });
+    assert!(permit_res.is_err());
+
+    let payload = PermitPayload {
+        asset_id:  asset.id.clone(),
+        grantor:   grantor.clone(),
+        grantee:   grantee.clone(),
+        value:     1024,
+        expiry:    None,
+        nonce:     0,
+        deadline:  10,
+        pubkey:    hex::encode(pubkey),
+        signature: sign(&message),
+    };
+
+    service
+        .permit(relayer_context.clone(), payload.clone())
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id,
+            grantor: grantor.clone(),
+            grantee,
+        })
+        .unwrap();
+    assert_eq!(allowance_res.value, 1024);
+
+    let nonce_res = service
+        .get_permit_nonce(context, GetPermitNoncePayload { user: grantor })
+        .unwrap();
+    assert_eq!(nonce_res.nonce, 1);
+
+    // replayed permits are rejected
+    let permit_res = service.permit(relayer_context.clone(), payload.clone());
+    assert!(permit_res.is_err());
+
+    // so are permits signed for other values
+    let permit_res = service.permit(relayer_context, PermitPayload {
+        value: 2048,
+        nonce: 1,
+        ..payload
+    });
+    assert!(permit_res.is_err());
+}
+
+#[test]
+fn test_migrate_legacy_allowance() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let to_context = mock_context(cycles_limit, to_address.clone());
+    let other = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    // balance record written before allowances had their own storage
+    let mut legacy_allowance = BTreeMap::new();
+    legacy_allowance.insert(to_address.clone(), 100);
+    legacy_allowance.insert(other.clone(), 50);
+    service
+        .sdk
+        .set_account_value(&caller, asset.id.clone(), AssetBalance {
+            value: supply,
+            legacy_allowance,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller.clone(),
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.value, 100);
+
+    service
+        .transfer_from(to_context, TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller.clone(),
+            recipient: to_address.clone(),
+            value:     30,
+            memo:      None,
+        })
+        .unwrap();
+
+    let asset_balance: AssetBalance = service
+        .sdk
+        .get_account_value(&caller, &asset.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(asset_balance.value, supply - 30);
+    assert!(asset_balance.legacy_allowance.is_empty());
+
+    let expect_allowances = vec![(to_address, 70), (other, 50)];
+    for (grantee, value) in expect_allowances {
+        let allowance_res = service
+            .get_allowance(context.clone(), GetAllowancePayload {
+                asset_id: asset.id.clone(),
+                grantor: caller.clone(),
+                grantee,
+            })
+            .unwrap();
+        assert_eq!(allowance_res.value, value);
+    }
+}
+
+#[test]
+fn test_mint() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .mint(context.clone(), MintPayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    let new_asset = service
+        .get_asset(context.clone(), GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(new_asset.supply, supply + 1024);
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1024);
+
+    // only issuer can mint
+    let to_context = mock_context(cycles_limit, to_address.clone());
+    let mint_res = service.mint(to_context, MintPayload {
+        asset_id: asset.id,
+        to:       to_address,
+        value:    1024,
+    });
+    assert!(mint_res.is_err());
+}
+
+#[test]
+fn test_supply_policy() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let create_res = service.create_asset(context.clone(), CreateAssetPayload {
+        name: "test".to_owned(),
+        symbol: "test".to_owned(),
+        supply: 1000,
+        max_supply: Some(999),
+        ..Default::default()
+    });
+    assert!(create_res.is_err());
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1000,
+            max_supply: Some(1300),
+            issuance: Some(IssuancePolicy {
+                period: 10,
+                limit:  200,
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let asset = service
+        .get_asset(context.clone(), GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(asset.max_supply, Some(1300));
+    assert_eq!(
+        asset.issuance,
+        Some(IssuancePolicy {
+            period: 10,
+            limit:  200,
+        })
+    );
+
+    let mint_payload = MintPayload {
+        asset_id: asset.id.clone(),
+        to:       caller.clone(),
+        value:    150,
+    };
+
+    // 150 in the window [0, 10), the second mint exceeds the limit
+    service.mint(context.clone(), mint_payload.clone()).unwrap();
+    let mint_res = service.mint(
+        mock_context_at(cycles_limit, caller.clone(), 9),
+        mint_payload.clone(),
+    );
+    assert!(mint_res.is_err());
+
+    // the window [10, 20) starts empty
+    service
+        .mint(
+            mock_context_at(cycles_limit, caller.clone(), 10),
+            mint_payload.clone(),
+        )
+        .unwrap();
+
+    // 1300 caps the supply regardless of the window
+    let mint_res = service.mint(mock_context_at(cycles_limit, caller, 20), mint_payload);
+    assert!(mint_res.is_err());
+
+    let asset = service
+        .get_asset(context, GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(asset.supply, 1300);
+}
+
+#[test]
+fn test_set_transfer_hook() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+    let hook = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(asset.transfer_hook, None);
+
+    // only issuer can attach a hook
+    let other = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let set_res =
+        service.set_transfer_hook(mock_context(cycles_limit, other), SetTransferHookPayload {
+            asset_id: asset.id.clone(),
+            hook:     Some(hook.clone()),
+        });
+    assert!(set_res.is_err());
+
+    service
+        .set_transfer_hook(context.clone(), SetTransferHookPayload {
+            asset_id: asset.id.clone(),
+            hook:     Some(hook.clone()),
+        })
+        .unwrap();
+    let new_asset = service
+        .get_asset(context.clone(), GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(new_asset.transfer_hook, Some(hook));
+
+    service
+        .set_transfer_hook(context.clone(), SetTransferHookPayload {
+            asset_id: asset.id.clone(),
+            hook:     None,
+        })
+        .unwrap();
+    let new_asset = service
+        .get_asset(context, GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(new_asset.transfer_hook, None);
+}
+
+#[test]
+fn test_transfer_hook() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let hook = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service_with(HookDispatcher);
+
+    let mut assets = Vec::new();
+    for _ in 0..2 {
+        let asset = service
+            .create_asset(context.clone(), CreateAssetPayload {
+                name: "test".to_owned(),
+                symbol: "test".to_owned(),
+                supply: 1024,
+                ..Default::default()
+            })
+            .unwrap();
+        assets.push(asset);
+    }
+    let (hooked, plain) = (assets[0].id.clone(), assets[1].id.clone());
+
+    service
+        .set_transfer_hook(context.clone(), SetTransferHookPayload {
+            asset_id: hooked.clone(),
+            hook:     Some(hook),
+        })
+        .unwrap();
+
+    // the hook accepts and its cycles are charged to the transfer
+    let mut cycles_used = Vec::new();
+    for asset_id in vec![hooked.clone(), plain] {
+        let transfer_context = mock_context(cycles_limit, caller.clone());
+        service
+            .transfer(transfer_context.clone(), TransferPayload {
+                asset_id,
+                to: to_address.clone(),
+                value: 100,
+                memo: None,
+            })
+            .unwrap();
+        cycles_used.push(transfer_context.get_cycles_used());
+    }
+    assert_eq!(cycles_used[0], cycles_used[1] + HOOK_CYCLES);
+
+    // the hook rejects
+    for &value in &[101, 42] {
+        let transfer_res = service.transfer(context.clone(), TransferPayload {
+            asset_id: hooked.clone(),
+            to: to_address.clone(),
+            value,
+            memo: None,
+        });
+        assert!(transfer_res.is_err());
+    }
+
+    let lock_res = service.lock_htlc(context.clone(), LockHtlcPayload {
+        asset_id:  hooked.clone(),
+        recipient: to_address.clone(),
+        value:     101,
+        hashlock:  sha256(b"secret").unwrap(),
+        timeout:   10,
+    });
+    assert!(lock_res.is_err());
+
+    let vesting_res = service.create_vesting(context.clone(), CreateVestingPayload {
+        asset_id:    hooked.clone(),
+        beneficiary: to_address.clone(),
+        value:       101,
+        start:       1,
+        cliff:       0,
+        duration:    10,
+    });
+    assert!(vesting_res.is_err());
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: hooked,
+            user:     to_address,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 100);
+}
+
+#[test]
+fn test_burn() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    service
+        .burn(context.clone(), BurnPayload {
+            asset_id: asset.id.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    let new_asset = service
+        .get_asset(context.clone(), GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(new_asset.supply, supply - 1024);
+
+    let balance_res = service
+        .get_balance(context.clone(), GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, supply - 1024);
+
+    let burn_res = service.burn(context, BurnPayload {
+        asset_id: asset.id,
+        value:    supply,
+    });
+    assert!(burn_res.is_err());
+}
+
+#[test]
+fn test_vesting() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let beneficiary = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let vesting = service
+        .create_vesting(context.clone(), CreateVestingPayload {
+            asset_id:    asset.id.clone(),
+            beneficiary: beneficiary.clone(),
+            value:       1000,
+            start:       10,
+            cliff:       10,
+            duration:    100,
+        })
+        .unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, supply - 1000);
+
+    // nothing is claimable before the cliff
+    let before_cliff = mock_context_at(cycles_limit, beneficiary.clone(), 15);
+    let status = service
+        .get_vesting(before_cliff.clone(), GetVestingPayload { id: vesting.id })
+        .unwrap();
+    assert_eq!(status.locked, 1000);
+    assert_eq!(status.claimable, 0);
+    let claim_res = service.claim_vested(before_cliff, ClaimVestedPayload { id: vesting.id });
+    assert!(claim_res.is_err());
+
+    // only the beneficiary can claim
+    let claim_res = service.claim_vested(
+        mock_context_at(cycles_limit, asset.issuer.clone(), 60),
+        ClaimVestedPayload { id: vesting.id },
+    );
+    assert!(claim_res.is_err());
+
+    let halfway = mock_context_at(cycles_limit, beneficiary.clone(), 60);
+    let page = service
+        .get_vestings(halfway.clone(), GetVestingsPayload {
+            beneficiary: beneficiary.clone(),
+            cursor:      0,
+            limit:       10,
+        })
+        .unwrap();
+    assert_eq!(page.vestings.len(), 1);
+    assert_eq!(page.vestings[0].locked, 500);
+    assert_eq!(page.vestings[0].claimable, 500);
+
+    service
+        .claim_vested(halfway, ClaimVestedPayload { id: vesting.id })
+        .unwrap();
+
+    let finished = mock_context_at(cycles_limit, beneficiary.clone(), 200);
+    service
+        .claim_vested(finished.clone(), ClaimVestedPayload { id: vesting.id })
+        .unwrap();
+
+    let status = service
+        .get_vesting(finished.clone(), GetVestingPayload { id: vesting.id })
+        .unwrap();
+    assert_eq!(status.locked, 0);
+    assert_eq!(status.claimable, 0);
+    assert_eq!(status.vesting.claimed, 1000);
+
+    let balance_res = service
+        .get_balance(finished, GetBalancePayload {
+            asset_id: asset.id,
+            user:     beneficiary,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1000);
+}
+
+#[test]
+fn test_distribution() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let holder_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let holder_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let payout = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "payout".to_owned(),
+            symbol: "PAY".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+    let shares = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "shares".to_owned(),
+            symbol: "SHR".to_owned(),
+            supply: 1000,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let transfers = vec![
+        (caller.clone(), holder_a.clone(), 300, 2),
+        (caller.clone(), holder_b.clone(), 100, 2),
+        // after the snapshot
+        (holder_a.clone(), holder_b.clone(), 100, 5),
+    ];
+    for (from, to, value, height) in transfers {
+        service
+            .transfer(
+                mock_context_at(cycles_limit, from, height),
+                TransferPayload {
+                    asset_id: shares.id.clone(),
+                    to,
+                    value,
+                    memo: None,
+                },
+            )
+            .unwrap();
+    }
+
+    let distribution = service
+        .distribute(
+            mock_context_at(cycles_limit, caller.clone(), 6),
+            DistributePayload {
+                asset_id:        payout.id.clone(),
+                holder_asset_id: shares.id,
+                value:           1000,
+                snapshot_height: 3,
+                claim_deadline:  10,
+            },
+        )
+        .unwrap();
+    assert_eq!(distribution.holder_supply, 1000);
+
+    let expect_shares = vec![(holder_a.clone(), 300), (holder_b, 100)];
+    for (user, share) in expect_shares {
+        let share_res = service
+            .get_distribution_share(context.clone(), GetDistributionSharePayload {
+                id:   distribution.id,
+                user: user.clone(),
+            })
+            .unwrap();
+        assert_eq!(share_res.share, share);
+        assert!(!share_res.claimed);
+
+        let claim_context = mock_context_at(cycles_limit, user.clone(), 7);
+        service
+            .claim_distribution(claim_context.clone(), ClaimDistributionPayload {
+                id: distribution.id,
+            })
+            .unwrap();
+        let claim_res = service.claim_distribution(claim_context, ClaimDistributionPayload {
+            id: distribution.id,
+        });
+        assert!(claim_res.is_err());
+
+        let balance_res = service
+            .get_balance(context.clone(), GetBalancePayload {
+                asset_id: payout.id.clone(),
+                user,
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, share);
+    }
+
+    let distribution = service
+        .get_distribution(context.clone(), GetDistributionPayload {
+            id: distribution.id,
+        })
+        .unwrap();
+    assert_eq!(distribution.claimed, 400);
+
+    // the rest stays locked until the claim deadline
+    let reclaim_res = service.reclaim_distribution(
+        mock_context_at(cycles_limit, caller.clone(), 10),
+        ReclaimDistributionPayload {
+            id: distribution.id,
+        },
+    );
+    assert!(reclaim_res.is_err());
+
+    let claim_res = service.claim_distribution(
+        mock_context_at(cycles_limit, caller.clone(), 11),
+        ClaimDistributionPayload {
+            id: distribution.id,
+        },
+    );
+    assert!(claim_res.is_err());
+
+    // only the sender can reclaim
+    let reclaim_res = service.reclaim_distribution(
+        mock_context_at(cycles_limit, holder_a, 11),
+        ReclaimDistributionPayload {
+            id: distribution.id,
+        },
+    );
+    assert!(reclaim_res.is_err());
+
+    let reclaim_context = mock_context_at(cycles_limit, caller.clone(), 11);
+    service
+        .reclaim_distribution(reclaim_context.clone(), ReclaimDistributionPayload {
+            id: distribution.id,
+        })
+        .unwrap();
+    let reclaim_res = service.reclaim_distribution(reclaim_context, ReclaimDistributionPayload {
+        id: distribution.id,
+    });
+    assert!(reclaim_res.is_err());
+
+    let balance_res = service
+        .get_balance(context.clone(), GetBalancePayload {
+            asset_id: payout.id,
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1024 * 1024 - 400);
+
+    let distribution = service
+        .get_distribution(context, GetDistributionPayload {
+            id: distribution.id,
+        })
+        .unwrap();
+    assert!(distribution.reclaimed);
+}
+
+#[test]
+fn test_audit_asset() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+    let user_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1000,
+            ..Default::default()
+        })
+        .unwrap();
+
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       user_a.clone(),
+            value:    100,
+            memo:     None,
+        })
+        .unwrap();
+    service
+        .mint(context.clone(), MintPayload {
+            asset_id: asset.id.clone(),
+            to:       user_a.clone(),
+            value:    50,
+        })
+        .unwrap();
+    service
+        .burn(context.clone(), BurnPayload {
+            asset_id: asset.id.clone(),
+            value:    30,
+        })
+        .unwrap();
+    let vesting = service
+        .create_vesting(context.clone(), CreateVestingPayload {
+            asset_id:    asset.id.clone(),
+            beneficiary: user_b.clone(),
+            value:       200,
+            start:       1,
+            cliff:       0,
+            duration:    10,
+        })
+        .unwrap();
+    service
+        .lock_htlc(context.clone(), LockHtlcPayload {
+            asset_id:  asset.id.clone(),
+            recipient: user_a.clone(),
+            value:     100,
+            hashlock:  sha256(b"secret").unwrap(),
+            timeout:   10,
+        })
+        .unwrap();
+    service
+        .claim_vested(
+            mock_context_at(cycles_limit, user_b, 6),
+            ClaimVestedPayload { id: vesting.id },
+        )
+        .unwrap();
+
+    let audit_payload = AuditAssetPayload {
+        asset_id:  asset.id.clone(),
+        full_scan: false,
+    };
+    let audit = service
+        .audit_asset(context.clone(), audit_payload.clone())
+        .unwrap();
+    assert_eq!(audit.supply, 1020);
+    assert_eq!(audit.circulating, 820);
+    assert_eq!(audit.locked, 200);
+    assert_eq!(audit.burned, 30);
+    assert_eq!(audit.discrepancy, 0);
+
+    let scan_payload = AuditAssetPayload {
+        full_scan: true,
+        ..audit_payload.clone()
+    };
+    let scan = service
+        .audit_asset(context.clone(), scan_payload.clone())
+        .unwrap();
+    assert_eq!(scan.circulating, audit.circulating);
+    assert_eq!(scan.locked, audit.locked);
+    assert_eq!(scan.discrepancy, 0);
+
+    // a balance written behind the service's back only shows up in a full scan
+    service
+        .sdk
+        .set_account_value(&user_a, asset.id, AssetBalance {
+            value: 1,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let audit = service.audit_asset(context.clone(), audit_payload).unwrap();
+    assert_eq!(audit.discrepancy, 0);
+
+    let scan = service.audit_asset(context, scan_payload).unwrap();
+    assert_eq!(scan.circulating, 671);
+    assert_eq!(scan.discrepancy, 149);
+}
+
+#[test]
+fn test_mul_div() {
+    let max = u128::max_value();
+
+    assert_eq!(mul_div(max, max, max), Some(max));
+    assert_eq!(mul_div(max, 2, 3), Some(max / 3 * 2));
+    assert_eq!(mul_div(1, max, 2), Some(max / 2));
+    assert_eq!(mul_div(max, 2, 1), None);
+    assert_eq!(mul_div(1, 1, 0), None);
+}
+
+#[test]
+fn test_htlc() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let recipient = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let preimage = b"secret".to_vec();
+    let hashlock = sha256(&preimage).unwrap();
+    assert_eq!(
+        hashlock.as_bytes(),
+        Bytes::from(
+            hex::decode("2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b")
+                .unwrap()
+        )
+    );
+    let lock_payload = LockHtlcPayload {
+        asset_id: asset.id.clone(),
+        recipient: recipient.clone(),
+        value: 100,
+        hashlock,
+        timeout: 10,
+    };
+
+    // claim with the preimage before timeout
+    let htlc = service
+        .lock_htlc(context.clone(), lock_payload.clone())
+        .unwrap();
+    assert_eq!(htlc.state, HtlcState::Locked);
+
+    let claim_res = service.claim_htlc(context.clone(), ClaimHtlcPayload {
+        id:       htlc.id,
+        preimage: hex::encode(b"wrong"),
+    });
+    assert!(claim_res.is_err());
+
+    service
+        .claim_htlc(
+            mock_context_at(cycles_limit, recipient.clone(), 10),
+            ClaimHtlcPayload {
+                id:       htlc.id,
+                preimage: hex::encode(&preimage),
+            },
+        )
+        .unwrap();
+
+    let htlc = service
+        .get_htlc(context.clone(), GetHtlcPayload { id: htlc.id })
+        .unwrap();
+    assert_eq!(htlc.state, HtlcState::Claimed);
+
+    // refund after timeout
+    let htlc = service.lock_htlc(context.clone(), lock_payload).unwrap();
+
+    let refund_res = service.refund_htlc(
+        mock_context_at(cycles_limit, caller.clone(), 10),
+        RefundHtlcPayload { id: htlc.id },
+    );
+    assert!(refund_res.is_err());
+
+    service
+        .refund_htlc(
+            mock_context_at(cycles_limit, caller.clone(), 11),
+            RefundHtlcPayload { id: htlc.id },
+        )
+        .unwrap();
+
+    let claim_res = service.claim_htlc(context.clone(), ClaimHtlcPayload {
+        id:       htlc.id,
+        preimage: hex::encode(&preimage),
+    });
+    assert!(claim_res.is_err());
+
+    let expect_balances = vec![(caller, supply - 100), (recipient, 100)];
+    for (user, balance) in expect_balances {
+        let balance_res = service
+            .get_balance(context.clone(), GetBalancePayload {
+                asset_id: asset.id.clone(),
+                user,
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, balance);
+    }
+}
+
+#[test]
+fn test_transfer_issuer() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let new_issuer = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let new_issuer_context = mock_context(cycles_limit, new_issuer.clone());
+
+    // nothing to accept yet
+    let accept_res = service.accept_issuer(new_issuer_context.clone(), AcceptIssuerPayload {
+        asset_id: asset.id.clone(),
+    });
+    assert!(accept_res.is_err());
+
+    service
+        .propose_issuer(context.clone(), ProposeIssuerPayload {
+            asset_id:   asset.id.clone(),
+            new_issuer: new_issuer.clone(),
+        })
+        .unwrap();
+    service
+        .cancel_issuer(context.clone(), CancelIssuerPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+    let accept_res = service.accept_issuer(new_issuer_context.clone(), AcceptIssuerPayload {
+        asset_id: asset.id.clone(),
+    });
+    assert!(accept_res.is_err());
+
+    service
+        .propose_issuer(context.clone(), ProposeIssuerPayload {
+            asset_id:   asset.id.clone(),
+            new_issuer: new_issuer.clone(),
+        })
+        .unwrap();
+
+    // only the proposed issuer can accept
+    let accept_res = service.accept_issuer(context.clone(), AcceptIssuerPayload {
+        asset_id: asset.id.clone(),
+    });
+    assert!(accept_res.is_err());
+
+    service
+        .accept_issuer(new_issuer_context, AcceptIssuerPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+
+    let new_asset = service
+        .get_asset(context.clone(), GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(new_asset.issuer, new_issuer);
+
+    // old issuer lost its authority
+    let mint_res = service.mint(context, MintPayload {
+        asset_id: asset.id,
+        to:       caller,
+        value:    1024,
+    });
+    assert!(mint_res.is_err());
+}
+
+#[test]
+fn test_freeze_account() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let holder = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let holder_context = mock_context(cycles_limit, holder.clone());
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       holder.clone(),
+            value:    1024,
+            memo:     None,
+        })
+        .unwrap();
+
+    // only issuer can freeze
+    let freeze_res = service.freeze_account(holder_context.clone(), FreezeAccountPayload {
+        asset_id: asset.id.clone(),
+        account:  caller.clone(),
+    });
+    assert!(freeze_res.is_err());
+
+    service
+        .freeze_account(context.clone(), FreezeAccountPayload {
+            asset_id: asset.id.clone(),
+            account:  holder.clone(),
+        })
+        .unwrap();
+
+    let frozen_res = service
+        .get_frozen(context.clone(), GetFrozenPayload {
+            asset_id: asset.id.clone(),
+            account:  holder.clone(),
+        })
+        .unwrap();
+    assert!(frozen_res.frozen);
+
+    let transfer_res = service.transfer(holder_context.clone(), TransferPayload {
+        asset_id: asset.id.clone(),
+        to:       caller.clone(),
+        value:    24,
+        memo:     None,
+    });
+    assert!(transfer_res.is_err());
+
+    let transfer_res = service.transfer(context.clone(), TransferPayload {
+        asset_id: asset.id.clone(),
+        to:       holder.clone(),
+        value:    24,
+        memo:     None,
+    });
+    assert!(transfer_res.is_err());
+
+    let approve_res = service.approve(holder_context.clone(), ApprovePayload {
+        asset_id: asset.id.clone(),
+        to:       caller.clone(),
+        value:    24,
+        expiry:   None,
+    });
+    assert!(approve_res.is_err());
+
+    service
+        .unfreeze_account(context.clone(), UnfreezeAccountPayload {
+            asset_id: asset.id.clone(),
+            account:  holder.clone(),
+        })
+        .unwrap();
+
+    service
+        .transfer(holder_context, TransferPayload {
+            asset_id: asset.id.clone(),
+            to:       caller,
+            value:    24,
+            memo:     None,
+        })
+        .unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user:     holder,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1000);
+}
+
+#[test]
+fn test_pause_asset() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+            expiry:   None,
+        })
+        .unwrap();
+
+    service
+        .pause_asset(context.clone(), PauseAssetPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+
+    let paused_res = service
+        .get_paused(context.clone(), GetPausedPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+    assert!(paused_res.paused);
+
+    let transfer_res = service.transfer(context.clone(), TransferPayload {
+        asset_id: asset.id.clone(),
+        to:       to_address.clone(),
+        value:    24,
+        memo:     None,
+    });
+    assert!(transfer_res.is_err());
+
+    let to_context = mock_context(cycles_limit, to_address.clone());
+    let transfer_from_res = service.transfer_from(to_context.clone(), TransferFromPayload {
+        asset_id:  asset.id.clone(),
+        sender:    caller.clone(),
+        recipient: to_address.clone(),
+        value:     24,
+        memo:      None,
+    });
+    assert!(transfer_from_res.is_err());
+
+    service
+        .unpause_asset(context.clone(), UnpauseAssetPayload {
+            asset_id: asset.id.clone(),
+        })
+        .unwrap();
+
+    service
+        .transfer_from(to_context, TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller,
+            recipient: to_address,
+            value:     24,
+            memo:      None,
+        })
+        .unwrap();
+
+    let paused_res = service
+        .get_paused(context, GetPausedPayload { asset_id: asset.id })
+        .unwrap();
+    assert!(!paused_res.paused);
+}
+
+#[test]
+fn test_nft() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+    let holder = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let holder_context = mock_context(cycles_limit, holder.clone());
+    let operator = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let operator_context = mock_context(cycles_limit, operator.clone());
+
+    let mut service = new_asset_service();
+
+    let collection = service
+        .create_collection(context.clone(), CreateCollectionPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+        })
+        .unwrap();
+    assert_eq!(collection.issuer, caller);
+
+    let mint_payload = MintNftPayload {
+        collection_id: collection.id.clone(),
+        token_id:      1,
+        to:            holder.clone(),
+        uri:           "ipfs://token/1".to_owned(),
+    };
+
+    // only the issuer can mint
+    let mint_res = service.mint_nft(holder_context.clone(), mint_payload.clone());
+    assert!(mint_res.is_err());
+
+    service
+        .mint_nft(context.clone(), mint_payload.clone())
+        .unwrap();
+    let mint_res = service.mint_nft(context.clone(), mint_payload);
+    assert!(mint_res.is_err());
+
+    let nft = service
+        .get_nft(context.clone(), GetNftPayload {
+            collection_id: collection.id.clone(),
+            token_id:      1,
+        })
+        .unwrap();
+    assert_eq!(nft.uri, "ipfs://token/1");
+    assert_eq!(nft.approved, None);
+
+    // the issuer is not the owner
+    let transfer_payload = TransferNftPayload {
+        collection_id: collection.id.clone(),
+        token_id:      1,
+        to:            operator.clone(),
+    };
+    let transfer_res = service.transfer_nft(context.clone(), transfer_payload.clone());
+    assert!(transfer_res.is_err());
+
+    // approved address can transfer once
+    service
+        .approve_nft(holder_context.clone(), ApproveNftPayload {
+            collection_id: collection.id.clone(),
+            token_id:      1,
+            to:            Some(caller.clone()),
+        })
+        .unwrap();
+    service
+        .transfer_nft(context.clone(), transfer_payload)
+        .unwrap();
+
+    let owner = service
+        .owner_of(context.clone(), OwnerOfPayload {
+            collection_id: collection.id.clone(),
+            token_id:      1,
+        })
+        .unwrap();
+    assert_eq!(owner.owner, operator);
+
+    let events = context.get_events();
+    let event: TransferNftEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.from, holder);
+    assert_eq!(event.to, operator);
+
+    let nft = service
+        .get_nft(context.clone(), GetNftPayload {
+            collection_id: collection.id.clone(),
+            token_id:      1,
+        })
+        .unwrap();
+    assert_eq!(nft.approved, None);
+
+    // operator can transfer every token of the owner
+    service
+        .set_operator(operator_context, SetOperatorPayload {
+            collection_id: collection.id.clone(),
+            operator:      holder.clone(),
+            approved:      true,
+        })
+        .unwrap();
+    let operator_res = service
+        .get_operator(context.clone(), GetOperatorPayload {
+            collection_id: collection.id.clone(),
+            owner:         operator.clone(),
+            operator:      holder,
+        })
+        .unwrap();
+    assert!(operator_res.approved);
+
+    service
+        .transfer_nft(holder_context, TransferNftPayload {
+            collection_id: collection.id.clone(),
+            token_id:      1,
+            to:            caller.clone(),
+        })
+        .unwrap();
+
+    for token_id in 2..5 {
+        service
+            .mint_nft(context.clone(), MintNftPayload {
+                collection_id: collection.id.clone(),
+                token_id,
+                to: caller.clone(),
+                uri: format!("ipfs://token/{}", token_id),
+            })
+            .unwrap();
+    }
+
+    let tokens = service
+        .tokens_of(context.clone(), TokensOfPayload {
+            collection_id: collection.id.clone(),
+            owner:         caller,
+            cursor:        0,
+            limit:         0,
+        })
+        .unwrap();
+    assert_eq!(tokens.token_count, 4);
+    assert_eq!(tokens.token_ids, vec![1, 2, 3, 4]);
+
+    let tokens = service
+        .tokens_of(context, TokensOfPayload {
+            collection_id: collection.id,
+            owner:         operator,
+            cursor:        0,
+            limit:         0,
+        })
+        .unwrap();
+    assert_eq!(tokens.token_count, 0);
+}
+
+const HOOK_CYCLES: u64 = 1_000;
+
+// Stands in for a riscv hook contract that exits with an error on transfers
+// above 100, answers "false" to a transfer of 42 and approves the rest
+struct HookDispatcher;
+
+impl Dispatcher for HookDispatcher {
+    fn read(&self, context: ServiceContext) -> ProtocolResult<ExecResp> {
+        assert_eq!(context.get_service_name(), "riscv");
+        assert_eq!(context.get_service_method(), "call");
+
+        let payload: TransferHookPayload =
+            serde_json::from_str(context.get_payload()).expect("hook payload");
+        let args: TransferHookArgs = serde_json::from_str(&payload.args).expect("hook args");
+
+        context.sub_cycles(HOOK_CYCLES)?;
+        if args.value > 100 {
+            return Ok(ExecResp {
+                ret:      "exit code 1".to_owned(),
+                is_error: true,
+            });
+        }
+
+        let ret = if args.value == 42 {
+            "false"
+        } else {
+            TRANSFER_HOOK_APPROVED
+        };
+        Ok(ExecResp {
+            ret:      serde_json::to_string(ret).unwrap(),
+            is_error: false,
+        })
+    }
+
+    fn write(&self, _context: ServiceContext) -> ProtocolResult<ExecResp> {
+        unimplemented!()
+    }
+}
+
+fn mock_chain_id() -> Hash {
+    Hash::digest(Bytes::from("test"))
+}
+
+// Answers the metadata reads of permit
+struct MetadataDispatcher;
+
+impl Dispatcher for MetadataDispatcher {
+    fn read(&self, context: ServiceContext) -> ProtocolResult<ExecResp> {
+        assert_eq!(context.get_service_name(), "metadata");
+        assert_eq!(context.get_service_method(), "get_metadata");
+
+        let metadata = Metadata {
+            chain_id:        mock_chain_id(),
+            common_ref:      "703873635a6b51513451".to_owned(),
+            timeout_gap:     20,
+            cycles_limit:    99_999_999,
+            cycles_price:    1,
+            interval:        3000,
+            verifier_list:   vec![],
+            propose_ratio:   10,
+            prevote_ratio:   10,
+            precommit_ratio: 10,
+            brake_ratio:     1,
+        };
+
+        Ok(ExecResp {
+            ret:      serde_json::to_string(&metadata).expect("metadata"),
+            is_error: false,
+        })
+    }
+
+    fn write(&self, _context: ServiceContext) -> ProtocolResult<ExecResp> {
+        unimplemented!()
+    }
+}
+
+fn new_asset_service() -> AssetService<
+    DefalutServiceSDK<
+        GeneralServiceState<MemoryDB>,
+        DefaultChainQuerier<MockStorage>,
+        NoopDispatcher,
+    >,
+> {
+    new_asset_service_with(NoopDispatcher {})
+}
+
+fn new_asset_service_with<D: Dispatcher + 'static>(
+    dispatcher: D,
+) -> AssetService<
+    DefalutServiceSDK<GeneralServiceState<MemoryDB>, DefaultChainQuerier<MockStorage>, D>,
+> {
+    let chain_db = DefaultChainQuerier::new(Arc::new(MockStorage {}));
+    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
+    let state = GeneralServiceState::new(trie);
+
+    let sdk = DefalutServiceSDK::new(Rc::new(RefCell::new(state)), Rc::new(chain_db), dispatcher);
+
+    AssetService::new(sdk).unwrap()
+}
+
+fn mock_genesis_asset(
+    id: Hash,
+    issuer: Address,
+    supply: u128,
+    allocations: Vec<GenesisAllocation>,
+) -> GenesisAsset {
+    GenesisAsset {
+        id,
+        name: "test".to_owned(),
+        symbol: "test".to_owned(),
+        supply,
+        issuer,
+        decimals: 0,
+        description: String::new(),
+        logo_uri: String::new(),
+        website: String::new(),
+        max_supply: None,
+        issuance: None,
+        allocations,
+    }
+}
+
+fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
+    mock_context_at(cycles_limit, caller, 1)
+}
+
+fn mock_context_at(cycles_limit: u64, caller: Address, height: u64) -> ServiceContext {
     let params = ServiceContextParams {
         tx_hash: None,
         nonce: None,
@@ -230,7 +2804,7 @@ fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
         cycles_price: 1,
         cycles_used: Rc::new(RefCell::new(0)),
         caller,
-        height: 1,
+        height,
         timestamp: 0,
         service_name: "service_name".to_owned(),
         service_method: "service_method".to_owned(),
@@ -11,138 +11,1283 @@ use protocol::ProtocolResult;
 /// Payload
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct InitGenesisPayload {
+    pub assets: Vec<GenesisAsset>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GenesisAsset {
+    pub id:          Hash,
+    pub name:        String,
+    pub symbol:      String,
+    #[serde(with = "u128_str")]
+    pub supply:      u128,
+    pub issuer:      Address,
+    #[serde(default)]
+    pub decimals:    u8,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub logo_uri:    String,
+    #[serde(default)]
+    pub website:     String,
+    #[serde(default, with = "opt_u128_str")]
+    pub max_supply:  Option<u128>,
+    #[serde(default)]
+    pub issuance:    Option<IssuancePolicy>,
+    pub allocations: Vec<GenesisAllocation>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GenesisAllocation {
+    pub address: Address,
+    #[serde(with = "u128_str")]
+    pub amount:  u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct CreateAssetPayload {
+    pub name:        String,
+    pub symbol:      String,
+    #[serde(with = "u128_str")]
+    pub supply:      u128,
+    #[serde(default)]
+    pub decimals:    u8,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub logo_uri:    String,
+    #[serde(default)]
+    pub website:     String,
+    #[serde(default, with = "opt_u128_str")]
+    pub max_supply:  Option<u128>,
+    #[serde(default)]
+    pub issuance:    Option<IssuancePolicy>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpdateAssetInfoPayload {
+    pub asset_id:    Hash,
+    pub description: String,
+    pub logo_uri:    String,
+    pub website:     String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpdateAssetInfoEvent {
+    pub asset_id:    Hash,
+    pub description: String,
+    pub logo_uri:    String,
+    pub website:     String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAssetPayload {
+    pub id: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PredictAssetIdPayload {
+    pub issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PredictAssetIdResponse {
+    pub asset_id: Hash,
+    pub issuer:   Address,
+    pub nonce:    u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ListAssetsPayload {
+    #[serde(default)]
+    pub cursor: u64,
+    #[serde(default)]
+    pub limit:  u64,
+    #[serde(default)]
+    pub issuer: Option<Address>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+// Assets created before the asset index existed are only listed once
+// `index_asset` has been called on them.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ListAssetsResponse {
+    pub assets:      Vec<Asset>,
+    pub next_cursor: Option<u64>,
+}
+
+// `hook` of none detaches the current hook
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetTransferHookPayload {
+    pub asset_id: Hash,
+    pub hook:     Option<Address>,
+}
+
+pub type SetTransferHookEvent = SetTransferHookPayload;
+
+// Same json layout as the riscv service's `ExecPayload`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferHookPayload {
+    pub address: Address,
+    pub args:    String,
+}
+
+// Passed to the hook contract as json args
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferHookArgs {
+    pub asset_id:  Hash,
+    pub sender:    Address,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferPayload {
+    pub asset_id: Hash,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+    // Payment reference for the recipient, such as an exchange deposit id
+    #[serde(default)]
+    pub memo:     Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferEvent {
+    pub asset_id: Hash,
+    pub from:     Address,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+    pub memo:     Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BatchTransferPayload {
+    pub asset_id:  Hash,
+    pub transfers: Vec<BatchTransferItem>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BatchTransferItem {
+    pub to:    Address,
+    #[serde(with = "u128_str")]
+    pub value: u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MintPayload {
+    pub asset_id: Hash,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MintEvent {
+    pub asset_id: Hash,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BurnPayload {
+    pub asset_id: Hash,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BurnEvent {
+    pub asset_id: Hash,
+    pub from:     Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CreateVestingPayload {
+    pub asset_id:    Hash,
+    pub beneficiary: Address,
+    #[serde(with = "u128_str")]
+    pub value:       u128,
+    pub start:       u64,
+    // Both counted in blocks from `start`
+    pub cliff:       u64,
+    pub duration:    u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimVestedPayload {
+    pub id: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimVestedEvent {
+    pub id:          u64,
+    pub asset_id:    Hash,
+    pub beneficiary: Address,
+    #[serde(with = "u128_str")]
+    pub value:       u128,
+}
+
+pub type GetVestingPayload = ClaimVestedPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetVestingsPayload {
+    pub beneficiary: Address,
+    #[serde(default)]
+    pub cursor:      u64,
+    #[serde(default)]
+    pub limit:       u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct VestingStatus {
+    pub vesting:   Vesting,
+    // Not vested yet at the current height
+    #[serde(with = "u128_str")]
+    pub locked:    u128,
+    // Vested but not claimed yet
+    #[serde(with = "u128_str")]
+    pub claimable: u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetVestingsResponse {
+    pub beneficiary: Address,
+    pub vestings:    Vec<VestingStatus>,
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DistributePayload {
+    // The asset paid out
+    pub asset_id:        Hash,
+    // Holders of this asset share the payout
+    pub holder_asset_id: Hash,
+    #[serde(with = "u128_str")]
+    pub value:           u128,
+    pub snapshot_height: u64,
+    // The last block height at which holders can claim, after it the sender
+    // can reclaim whatever is left
+    pub claim_deadline:  u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimDistributionPayload {
+    pub id: u64,
+}
+
+pub type ReclaimDistributionPayload = ClaimDistributionPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimDistributionEvent {
+    pub id:       u64,
+    pub asset_id: Hash,
+    pub user:     Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReclaimDistributionEvent {
+    pub id:       u64,
+    pub asset_id: Hash,
+    pub sender:   Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+pub type GetDistributionPayload = ClaimDistributionPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetDistributionSharePayload {
+    pub id:   u64,
+    pub user: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetDistributionShareResponse {
+    pub id:      u64,
+    pub user:    Address,
+    #[serde(with = "u128_str")]
+    pub share:   u128,
+    pub claimed: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LockHtlcPayload {
+    pub asset_id:  Hash,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+    // SHA-256 hash of the preimage, the same lock other chains' htlcs use
+    pub hashlock:  Hash,
+    // The last block height at which the htlc can be claimed
+    pub timeout:   u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimHtlcPayload {
+    pub id:       u64,
+    // Hex encoded
+    pub preimage: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ClaimHtlcEvent {
+    pub id:        u64,
+    pub asset_id:  Hash,
+    pub hashlock:  Hash,
+    pub preimage:  String,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RefundHtlcPayload {
+    pub id: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RefundHtlcEvent {
+    pub id:       u64,
+    pub asset_id: Hash,
+    pub hashlock: Hash,
+    pub sender:   Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+pub type GetHtlcPayload = RefundHtlcPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CreateCollectionPayload {
+    pub name:   String,
+    pub symbol: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetCollectionPayload {
+    pub id: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MintNftPayload {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub to:            Address,
+    pub uri:           String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MintNftEvent {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub to:            Address,
+    pub uri:           String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferNftPayload {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub to:            Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferNftEvent {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub from:          Address,
+    pub to:            Address,
+}
+
+// `to` of none revokes the approval
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApproveNftPayload {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub to:            Option<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApproveNftEvent {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub owner:         Address,
+    pub approved:      Option<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetOperatorPayload {
+    pub collection_id: Hash,
+    pub operator:      Address,
+    pub approved:      bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetOperatorEvent {
+    pub collection_id: Hash,
+    pub owner:         Address,
+    pub operator:      Address,
+    pub approved:      bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetOperatorPayload {
+    pub collection_id: Hash,
+    pub owner:         Address,
+    pub operator:      Address,
+}
+
+pub type GetOperatorResponse = SetOperatorEvent;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetNftPayload {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+}
+
+pub type OwnerOfPayload = GetNftPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OwnerOfResponse {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub owner:         Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TokensOfPayload {
+    pub collection_id: Hash,
+    pub owner:         Address,
+    #[serde(default)]
+    pub cursor:        u64,
+    #[serde(default)]
+    pub limit:         u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TokensOfResponse {
+    pub collection_id: Hash,
+    pub owner:         Address,
+    pub token_count:   u64,
+    pub token_ids:     Vec<u64>,
+    pub next_cursor:   Option<u64>,
+}
+
+// Adds an asset and those of `holders` with a non-zero balance to the indexes,
+// already indexed entries are left as they are.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct IndexAssetPayload {
+    pub asset_id: Hash,
+    #[serde(default)]
+    pub holders:  Vec<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AuditAssetPayload {
+    pub asset_id:  Hash,
+    #[serde(default)]
+    pub full_scan: bool,
+}
+
+// `discrepancy` is the absolute difference between `supply` and
+// `circulating + locked`, it's zero for a consistent asset.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AuditAssetResponse {
+    pub asset_id:    Hash,
+    pub full_scan:   bool,
+    #[serde(with = "u128_str")]
+    pub supply:      u128,
+    #[serde(with = "u128_str")]
+    pub circulating: u128,
+    #[serde(with = "u128_str")]
+    pub locked:      u128,
+    #[serde(with = "u128_str")]
+    pub burned:      u128,
+    #[serde(with = "u128_str")]
+    pub discrepancy: u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProposeIssuerPayload {
+    pub asset_id:   Hash,
+    pub new_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProposeIssuerEvent {
+    pub asset_id:   Hash,
+    pub issuer:     Address,
+    pub new_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AcceptIssuerPayload {
+    pub asset_id: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AcceptIssuerEvent {
+    pub asset_id:   Hash,
+    pub old_issuer: Address,
+    pub new_issuer: Address,
+}
+
+pub type CancelIssuerPayload = AcceptIssuerPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CancelIssuerEvent {
+    pub asset_id:   Hash,
+    pub issuer:     Address,
+    pub new_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FreezeAccountPayload {
+    pub asset_id: Hash,
+    pub account:  Address,
+}
+
+pub type UnfreezeAccountPayload = FreezeAccountPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FreezeAccountEvent {
+    pub asset_id: Hash,
+    pub account:  Address,
+    pub frozen:   bool,
+}
+
+pub type GetFrozenPayload = FreezeAccountPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetFrozenResponse {
+    pub asset_id: Hash,
+    pub account:  Address,
+    pub frozen:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PauseAssetPayload {
+    pub asset_id: Hash,
+}
+
+pub type UnpauseAssetPayload = PauseAssetPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PauseAssetEvent {
+    pub asset_id: Hash,
+    pub paused:   bool,
+}
+
+pub type GetPausedPayload = PauseAssetPayload;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetPausedResponse {
+    pub asset_id: Hash,
+    pub paused:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApprovePayload {
+    pub asset_id: Hash,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+    // The last block height at which the allowance can be spent
+    #[serde(default)]
+    pub expiry:   Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApproveEvent {
+    pub asset_id: Hash,
+    pub grantor:  Address,
+    pub grantee:  Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+    pub expiry:   Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct IncreaseAllowancePayload {
+    pub asset_id: Hash,
+    pub to:       Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+}
+
+pub type DecreaseAllowancePayload = IncreaseAllowancePayload;
+
+// An approval signed off-chain by the grantor, anyone can submit it. The
+// signature covers the keccak hash of the rlp encoded `PermitMessage`, which
+// is bound to `PERMIT_DOMAIN` and the chain id so it can't be replayed on
+// another chain or method.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PermitPayload {
+    pub asset_id:  Hash,
+    pub grantor:   Address,
+    pub grantee:   Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+    #[serde(default)]
+    pub expiry:    Option<u64>,
+    pub nonce:     u64,
+    // The last block height at which the permit can be submitted
+    pub deadline:  u64,
+    // Hex encoded secp256k1 public key and signature of the grantor
+    pub pubkey:    String,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PermitMessage {
+    pub domain:   String,
+    pub chain_id: Hash,
+    pub asset_id: Hash,
+    pub grantor:  Address,
+    pub grantee:  Address,
+    pub value:    u128,
+    pub expiry:   Option<u64>,
+    pub nonce:    u64,
+    pub deadline: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetPermitNoncePayload {
+    pub user: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetPermitNonceResponse {
+    pub user:  Address,
+    pub nonce: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferFromPayload {
+    pub asset_id:  Hash,
+    pub sender:    Address,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+    #[serde(default)]
+    pub memo:      Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransferFromEvent {
+    pub asset_id:  Hash,
+    pub caller:    Address,
+    pub sender:    Address,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+    pub memo:      Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetBalancePayload {
+    pub asset_id: Hash,
+    pub user:     Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetBalanceResponse {
+    pub asset_id: Hash,
+    pub user:     Address,
+    #[serde(with = "u128_str")]
+    pub balance:  u128,
+}
+
+// Either the balances of one user in many assets, or of many users in one
+// asset
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum GetBalancesPayload {
+    ByUser {
+        user:      Address,
+        asset_ids: Vec<Hash>,
+    },
+    ByAsset {
+        asset_id: Hash,
+        users:    Vec<Address>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetBalanceAtPayload {
+    pub asset_id: Hash,
+    pub user:     Address,
+    pub height:   u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetBalanceAtResponse {
+    pub asset_id: Hash,
+    pub user:     Address,
+    pub height:   u64,
+    #[serde(with = "u128_str")]
+    pub balance:  u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetSupplyAtPayload {
+    pub asset_id: Hash,
+    pub height:   u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetSupplyAtResponse {
+    pub asset_id: Hash,
+    pub height:   u64,
+    #[serde(with = "u128_str")]
+    pub supply:   u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetHoldersPayload {
+    pub asset_id: Hash,
+    #[serde(default)]
+    pub cursor:   u64,
+    #[serde(default)]
+    pub limit:    u64,
+}
+
+// Accounts holding a balance from before the holder index existed are listed
+// after their next balance change, or once passed to `index_asset`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetHoldersResponse {
+    pub asset_id:     Hash,
+    pub holder_count: u64,
+    pub holders:      Vec<Holder>,
+    pub next_cursor:  Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Holder {
+    pub user:    Address,
+    #[serde(with = "u128_str")]
+    pub balance: u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAllowancePayload {
+    pub asset_id: Hash,
+    pub grantor:  Address,
+    pub grantee:  Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAllowancesPayload {
+    pub allowances: Vec<GetAllowancePayload>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAllowanceResponse {
+    pub asset_id: Hash,
+    pub grantor:  Address,
+    pub grantee:  Address,
+    #[serde(with = "u128_str")]
+    pub value:    u128,
+    pub expiry:   Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Asset {
+    pub id:            Hash,
+    pub name:          String,
+    pub symbol:        String,
+    #[serde(with = "u128_str")]
+    pub supply:        u128,
+    pub issuer:        Address,
+    pub decimals:      u8,
+    pub description:   String,
+    pub logo_uri:      String,
+    pub website:       String,
+    // Both are fixed at creation, none means unlimited
+    #[serde(with = "opt_u128_str")]
+    pub max_supply:    Option<u128>,
+    pub issuance:      Option<IssuancePolicy>,
+    // RISC-V contract called before every transfer of this asset
+    pub transfer_hook: Option<Address>,
+}
+
+// At most `limit` can be minted within each window of `period` blocks, windows
+// start at multiples of `period`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct IssuancePolicy {
+    pub period: u64,
+    #[serde(with = "u128_str")]
+    pub limit:  u128,
+}
+
+// Running totals of an asset: the sum of all balances, amounts held by
+// vestings, htlcs and distributions, and everything burned so far.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetTotals {
+    pub circulating: u128,
+    pub locked:      u128,
+    pub burned:      u128,
+}
+
+// Amount minted in the window starting at `period * policy.period`
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssuanceWindow {
+    pub period: u64,
+    pub issued: u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Collection {
     pub id:     Hash,
     pub name:   String,
     pub symbol: String,
-    pub supply: u64,
     pub issuer: Address,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct CreateAssetPayload {
-    pub name:   String,
-    pub symbol: String,
-    pub supply: u64,
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Nft {
+    pub collection_id: Hash,
+    pub token_id:      u64,
+    pub owner:         Address,
+    pub uri:           String,
+    pub approved:      Option<Address>,
+}
+
+// Vested amount grows linearly from `start` to `start + duration`, nothing
+// can be claimed before `start + cliff`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Vesting {
+    pub id:          u64,
+    pub asset_id:    Hash,
+    pub grantor:     Address,
+    pub beneficiary: Address,
+    #[serde(with = "u128_str")]
+    pub total:       u128,
+    #[serde(with = "u128_str")]
+    pub claimed:     u128,
+    pub start:       u64,
+    pub cliff:       u64,
+    pub duration:    u64,
+}
+
+impl Vesting {
+    pub fn vested(&self, height: u64) -> u128 {
+        let elapsed = height.saturating_sub(self.start);
+        if elapsed < self.cliff {
+            return 0;
+        }
+        if elapsed >= self.duration {
+            return self.total;
+        }
+
+        // Split `total` so that `total * elapsed` can not overflow
+        let (elapsed, duration) = (u128::from(elapsed), u128::from(self.duration));
+        let quotient = self.total / duration;
+        let remainder = self.total % duration;
+        quotient * elapsed + remainder * elapsed / duration
+    }
+}
+
+// `holder_supply` is the supply of the holder asset at the snapshot, every
+// holder gets `value * balance / holder_supply`. Once `claim_deadline` has
+// passed, the sender can reclaim `value - claimed`, which covers the shares of
+// locked balances, rounding dust and unclaimed shares.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Distribution {
+    pub id:              u64,
+    pub asset_id:        Hash,
+    pub holder_asset_id: Hash,
+    pub sender:          Address,
+    #[serde(with = "u128_str")]
+    pub value:           u128,
+    pub snapshot_height: u64,
+    #[serde(with = "u128_str")]
+    pub holder_supply:   u128,
+    #[serde(with = "u128_str")]
+    pub claimed:         u128,
+    pub claim_deadline:  u64,
+    pub reclaimed:       bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Htlc {
+    pub id:        u64,
+    pub asset_id:  Hash,
+    pub sender:    Address,
+    pub recipient: Address,
+    #[serde(with = "u128_str")]
+    pub value:     u128,
+    pub hashlock:  Hash,
+    pub timeout:   u64,
+    pub state:     HtlcState,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HtlcState {
+    Locked,
+    Claimed,
+    Refunded,
+}
+
+// Value of a balance or supply after all changes in the block at `height`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub value:  u128,
+}
+
+// Allowances live in their own records, `legacy_allowance` only holds the
+// approvals embedded by older versions until they are migrated.
+#[derive(Default)]
+pub struct AssetBalance {
+    pub value:            u128,
+    pub legacy_allowance: BTreeMap<Address, u128>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Allowance {
+    pub value:  u128,
+    pub expiry: Option<u64>,
+}
+
+impl Allowance {
+    pub fn is_expired(&self, height: u64) -> bool {
+        self.expiry.map_or(false, |expiry| height > expiry)
+    }
+}
+
+struct AllowanceCodec {
+    pub addr:  Address,
+    pub total: u128,
+}
+
+// Amounts are encoded as big-endian bytes without leading zeros, which is
+// exactly how rlp encodes a `u64`, so records written while amounts were
+// `u64` decode unchanged.
+struct U128Codec(u128);
+
+// Fields appended to a record after it was first stored are missing from the
+// old encoding, decode them as default so the old records stay readable.
+fn val_at_or_default<T: rlp::Decodable + Default>(
+    rlp: &rlp::Rlp,
+    index: usize,
+) -> Result<T, rlp::DecoderError> {
+    if index < rlp.item_count()? {
+        rlp.val_at(index)
+    } else {
+        Ok(T::default())
+    }
+}
+
+impl rlp::Decodable for U128Codec {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        let bytes = rlp.data()?;
+
+        if bytes.len() > 16 {
+            return Err(rlp::DecoderError::RlpIsTooBig);
+        }
+        if !bytes.is_empty() && bytes[0] == 0 {
+            return Err(rlp::DecoderError::RlpInvalidIndirection);
+        }
+
+        let value = bytes
+            .iter()
+            .fold(0u128, |acc, byte| (acc << 8) | u128::from(*byte));
+        Ok(U128Codec(value))
+    }
+}
+
+impl rlp::Encodable for U128Codec {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        let leading_empty_bytes = self.0.leading_zeros() as usize / 8;
+        let buffer = self.0.to_be_bytes();
+        s.encoder().encode_value(&buffer[leading_empty_bytes..]);
+    }
+}
+
+impl rlp::Decodable for Asset {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            id:            rlp.at(0)?.as_val()?,
+            name:          rlp.at(1)?.as_val()?,
+            symbol:        rlp.at(2)?.as_val()?,
+            supply:        rlp.val_at::<U128Codec>(3)?.0,
+            issuer:        rlp.at(4)?.as_val()?,
+            decimals:      val_at_or_default(rlp, 5)?,
+            description:   val_at_or_default(rlp, 6)?,
+            logo_uri:      val_at_or_default(rlp, 7)?,
+            website:       val_at_or_default(rlp, 8)?,
+            max_supply:    val_at_or_default::<Option<U128Codec>>(rlp, 9)?.map(|v| v.0),
+            issuance:      val_at_or_default(rlp, 10)?,
+            transfer_hook: val_at_or_default(rlp, 11)?,
+        })
+    }
+}
+
+impl rlp::Encodable for Asset {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(12)
+            .append(&self.id)
+            .append(&self.name)
+            .append(&self.symbol)
+            .append(&U128Codec(self.supply))
+            .append(&self.issuer)
+            .append(&self.decimals)
+            .append(&self.description)
+            .append(&self.logo_uri)
+            .append(&self.website)
+            .append(&self.max_supply.map(U128Codec))
+            .append(&self.issuance)
+            .append(&self.transfer_hook);
+    }
+}
+
+impl FixedCodec for Asset {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
+
+impl rlp::Decodable for IssuancePolicy {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            period: rlp.val_at(0)?,
+            limit:  rlp.val_at::<U128Codec>(1)?.0,
+        })
+    }
+}
+
+impl rlp::Encodable for IssuancePolicy {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2)
+            .append(&self.period)
+            .append(&U128Codec(self.limit));
+    }
+}
+
+impl rlp::Decodable for AssetTotals {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            circulating: rlp.val_at::<U128Codec>(0)?.0,
+            locked:      rlp.val_at::<U128Codec>(1)?.0,
+            burned:      rlp.val_at::<U128Codec>(2)?.0,
+        })
+    }
+}
+
+impl rlp::Encodable for AssetTotals {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(3)
+            .append(&U128Codec(self.circulating))
+            .append(&U128Codec(self.locked))
+            .append(&U128Codec(self.burned));
+    }
+}
+
+impl FixedCodec for AssetTotals {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
+
+impl rlp::Decodable for IssuanceWindow {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            period: rlp.val_at(0)?,
+            issued: rlp.val_at::<U128Codec>(1)?.0,
+        })
+    }
+}
+
+impl rlp::Encodable for IssuanceWindow {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2)
+            .append(&self.period)
+            .append(&U128Codec(self.issued));
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GetAssetPayload {
-    pub id: Hash,
+impl FixedCodec for IssuanceWindow {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct TransferPayload {
-    pub asset_id: Hash,
-    pub to:       Address,
-    pub value:    u64,
+impl rlp::Decodable for AllowanceCodec {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            addr:  rlp.at(0)?.as_val()?,
+            total: rlp.val_at::<U128Codec>(1)?.0,
+        })
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct TransferEvent {
-    pub asset_id: Hash,
-    pub from:     Address,
-    pub to:       Address,
-    pub value:    u64,
+impl rlp::Encodable for AllowanceCodec {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2)
+            .append(&self.addr)
+            .append(&U128Codec(self.total));
+    }
 }
 
-pub type ApprovePayload = TransferPayload;
+impl rlp::Decodable for AssetBalance {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        let value = rlp.val_at::<U128Codec>(0)?.0;
+        let codec_list: Vec<AllowanceCodec> = rlp::decode_list(rlp.at(1)?.as_raw());
+        let mut legacy_allowance = BTreeMap::new();
+        for v in codec_list {
+            legacy_allowance.insert(v.addr, v.total);
+        }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct ApproveEvent {
-    pub asset_id: Hash,
-    pub grantor:  Address,
-    pub grantee:  Address,
-    pub value:    u64,
+        Ok(AssetBalance {
+            value,
+            legacy_allowance,
+        })
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct TransferFromPayload {
-    pub asset_id:  Hash,
-    pub sender:    Address,
-    pub recipient: Address,
-    pub value:     u64,
-}
+impl rlp::Encodable for AssetBalance {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2);
+        s.append(&U128Codec(self.value));
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct TransferFromEvent {
-    pub asset_id:  Hash,
-    pub caller:    Address,
-    pub sender:    Address,
-    pub recipient: Address,
-    pub value:     u64,
+        let mut codec_list = Vec::with_capacity(self.legacy_allowance.len());
+
+        for (address, allowance) in self.legacy_allowance.iter() {
+            let fixed_codec = AllowanceCodec {
+                addr:  address.clone(),
+                total: *allowance,
+            };
+
+            codec_list.push(fixed_codec);
+        }
+
+        s.append_list(&codec_list);
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GetBalancePayload {
-    pub asset_id: Hash,
-    pub user:     Address,
+impl FixedCodec for AssetBalance {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GetBalanceResponse {
-    pub asset_id: Hash,
-    pub user:     Address,
-    pub balance:  u64,
+impl rlp::Decodable for Allowance {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            value:  rlp.val_at::<U128Codec>(0)?.0,
+            expiry: val_at_or_default(rlp, 1)?,
+        })
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GetAllowancePayload {
-    pub asset_id: Hash,
-    pub grantor:  Address,
-    pub grantee:  Address,
+impl rlp::Encodable for Allowance {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2)
+            .append(&U128Codec(self.value))
+            .append(&self.expiry);
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GetAllowanceResponse {
-    pub asset_id: Hash,
-    pub grantor:  Address,
-    pub grantee:  Address,
-    pub value:    u64,
+impl FixedCodec for Allowance {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
-pub struct Asset {
-    pub id:     Hash,
-    pub name:   String,
-    pub symbol: String,
-    pub supply: u64,
-    pub issuer: Address,
+impl rlp::Encodable for PermitMessage {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(9)
+            .append(&self.domain)
+            .append(&self.chain_id)
+            .append(&self.asset_id)
+            .append(&self.grantor)
+            .append(&self.grantee)
+            .append(&U128Codec(self.value))
+            .append(&self.expiry)
+            .append(&self.nonce)
+            .append(&self.deadline);
+    }
 }
 
-pub struct AssetBalance {
-    pub value:     u64,
-    pub allowance: BTreeMap<Address, u64>,
+impl rlp::Decodable for PermitMessage {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            domain:   rlp.val_at(0)?,
+            chain_id: rlp.val_at(1)?,
+            asset_id: rlp.val_at(2)?,
+            grantor:  rlp.val_at(3)?,
+            grantee:  rlp.val_at(4)?,
+            value:    rlp.val_at::<U128Codec>(5)?.0,
+            expiry:   rlp.val_at(6)?,
+            nonce:    rlp.val_at(7)?,
+            deadline: rlp.val_at(8)?,
+        })
+    }
 }
 
-struct AllowanceCodec {
-    pub addr:  Address,
-    pub total: u64,
+impl FixedCodec for PermitMessage {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
 }
 
-impl rlp::Decodable for Asset {
+impl rlp::Decodable for Collection {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         Ok(Self {
-            id:     rlp.at(0)?.as_val()?,
-            name:   rlp.at(1)?.as_val()?,
-            symbol: rlp.at(2)?.as_val()?,
-            supply: rlp.at(3)?.as_val()?,
-            issuer: rlp.at(4)?.as_val()?,
+            id:     rlp.val_at(0)?,
+            name:   rlp.val_at(1)?,
+            symbol: rlp.val_at(2)?,
+            issuer: rlp.val_at(3)?,
         })
     }
 }
 
-impl rlp::Encodable for Asset {
+impl rlp::Encodable for Collection {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(5)
+        s.begin_list(4)
             .append(&self.id)
             .append(&self.name)
             .append(&self.symbol)
-            .append(&self.supply)
             .append(&self.issuer);
     }
 }
 
-impl FixedCodec for Asset {
+impl FixedCodec for Collection {
     fn encode_fixed(&self) -> ProtocolResult<Bytes> {
         Ok(Bytes::from(rlp::encode(self)))
     }
@@ -152,55 +1297,197 @@ impl FixedCodec for Asset {
     }
 }
 
-impl rlp::Decodable for AllowanceCodec {
+impl rlp::Decodable for Nft {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         Ok(Self {
-            addr:  rlp.at(0)?.as_val()?,
-            total: rlp.at(1)?.as_val()?,
+            collection_id: rlp.val_at(0)?,
+            token_id:      rlp.val_at(1)?,
+            owner:         rlp.val_at(2)?,
+            uri:           rlp.val_at(3)?,
+            approved:      rlp.val_at(4)?,
         })
     }
 }
 
-impl rlp::Encodable for AllowanceCodec {
+impl rlp::Encodable for Nft {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(2).append(&self.addr).append(&self.total);
+        s.begin_list(5)
+            .append(&self.collection_id)
+            .append(&self.token_id)
+            .append(&self.owner)
+            .append(&self.uri)
+            .append(&self.approved);
     }
 }
 
-impl rlp::Decodable for AssetBalance {
+impl FixedCodec for Nft {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
+
+impl rlp::Decodable for Vesting {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
-        let value = rlp.at(0)?.as_val()?;
-        let codec_list: Vec<AllowanceCodec> = rlp::decode_list(rlp.at(1)?.as_raw());
-        let mut allowance = BTreeMap::new();
-        for v in codec_list {
-            allowance.insert(v.addr, v.total);
-        }
+        Ok(Self {
+            id:          rlp.val_at(0)?,
+            asset_id:    rlp.val_at(1)?,
+            grantor:     rlp.val_at(2)?,
+            beneficiary: rlp.val_at(3)?,
+            total:       rlp.val_at::<U128Codec>(4)?.0,
+            claimed:     rlp.val_at::<U128Codec>(5)?.0,
+            start:       rlp.val_at(6)?,
+            cliff:       rlp.val_at(7)?,
+            duration:    rlp.val_at(8)?,
+        })
+    }
+}
 
-        Ok(AssetBalance { value, allowance })
+impl rlp::Encodable for Vesting {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(9)
+            .append(&self.id)
+            .append(&self.asset_id)
+            .append(&self.grantor)
+            .append(&self.beneficiary)
+            .append(&U128Codec(self.total))
+            .append(&U128Codec(self.claimed))
+            .append(&self.start)
+            .append(&self.cliff)
+            .append(&self.duration);
     }
 }
 
-impl rlp::Encodable for AssetBalance {
+impl FixedCodec for Vesting {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
+
+impl rlp::Decodable for Distribution {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            id:              rlp.val_at(0)?,
+            asset_id:        rlp.val_at(1)?,
+            holder_asset_id: rlp.val_at(2)?,
+            sender:          rlp.val_at(3)?,
+            value:           rlp.val_at::<U128Codec>(4)?.0,
+            snapshot_height: rlp.val_at(5)?,
+            holder_supply:   rlp.val_at::<U128Codec>(6)?.0,
+            claimed:         rlp.val_at::<U128Codec>(7)?.0,
+            claim_deadline:  rlp.val_at(8)?,
+            reclaimed:       rlp.val_at(9)?,
+        })
+    }
+}
+
+impl rlp::Encodable for Distribution {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(2);
-        s.append(&self.value);
+        s.begin_list(10)
+            .append(&self.id)
+            .append(&self.asset_id)
+            .append(&self.holder_asset_id)
+            .append(&self.sender)
+            .append(&U128Codec(self.value))
+            .append(&self.snapshot_height)
+            .append(&U128Codec(self.holder_supply))
+            .append(&U128Codec(self.claimed))
+            .append(&self.claim_deadline)
+            .append(&self.reclaimed);
+    }
+}
 
-        let mut codec_list = Vec::with_capacity(self.allowance.len());
+impl FixedCodec for Distribution {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
 
-        for (address, allowance) in self.allowance.iter() {
-            let fixed_codec = AllowanceCodec {
-                addr:  address.clone(),
-                total: *allowance,
-            };
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
 
-            codec_list.push(fixed_codec);
+impl rlp::Decodable for HtlcState {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        match rlp.as_val::<u8>()? {
+            0 => Ok(HtlcState::Locked),
+            1 => Ok(HtlcState::Claimed),
+            2 => Ok(HtlcState::Refunded),
+            _ => Err(rlp::DecoderError::Custom("invalid htlc state")),
         }
+    }
+}
 
-        s.append_list(&codec_list);
+impl rlp::Encodable for HtlcState {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.append(&(*self as u8));
     }
 }
 
-impl FixedCodec for AssetBalance {
+impl rlp::Decodable for Htlc {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            id:        rlp.val_at(0)?,
+            asset_id:  rlp.val_at(1)?,
+            sender:    rlp.val_at(2)?,
+            recipient: rlp.val_at(3)?,
+            value:     rlp.val_at::<U128Codec>(4)?.0,
+            hashlock:  rlp.val_at(5)?,
+            timeout:   rlp.val_at(6)?,
+            state:     rlp.val_at(7)?,
+        })
+    }
+}
+
+impl rlp::Encodable for Htlc {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(8)
+            .append(&self.id)
+            .append(&self.asset_id)
+            .append(&self.sender)
+            .append(&self.recipient)
+            .append(&U128Codec(self.value))
+            .append(&self.hashlock)
+            .append(&self.timeout)
+            .append(&self.state);
+    }
+}
+
+impl FixedCodec for Htlc {
+    fn encode_fixed(&self) -> ProtocolResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_fixed(bytes: Bytes) -> ProtocolResult<Self> {
+        Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
+    }
+}
+
+impl rlp::Decodable for Checkpoint {
+    fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(Self {
+            height: rlp.val_at(0)?,
+            value:  rlp.val_at::<U128Codec>(1)?.0,
+        })
+    }
+}
+
+impl rlp::Encodable for Checkpoint {
+    fn rlp_append(&self, s: &mut rlp::RlpStream) {
+        s.begin_list(2)
+            .append(&self.height)
+            .append(&U128Codec(self.value));
+    }
+}
+
+impl FixedCodec for Checkpoint {
     fn encode_fixed(&self) -> ProtocolResult<Bytes> {
         Ok(Bytes::from(rlp::encode(self)))
     }
@@ -209,3 +1496,58 @@ impl FixedCodec for AssetBalance {
         Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
     }
 }
+
+// Amounts are serialized as decimal strings so that json clients without
+// 128-bit integers do not lose precision, plain json numbers are still
+// accepted as input.
+mod u128_str {
+    use std::fmt;
+
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        deserializer.deserialize_any(U128Visitor)
+    }
+
+    struct U128Visitor;
+
+    impl<'de> de::Visitor<'de> for U128Visitor {
+        type Value = u128;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a decimal string or an unsigned integer")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<u128, E> {
+            Ok(u128::from(value))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<u128, E> {
+            value.parse().map_err(E::custom)
+        }
+    }
+}
+
+mod opt_u128_str {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize, Serialize)]
+    struct U128Str(#[serde(with = "super::u128_str")] u128);
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<u128>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(U128Str).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u128>, D::Error> {
+        Ok(Option::<U128Str>::deserialize(deserializer)?.map(|v| v.0))
+    }
+}
@@ -10,24 +10,30 @@ use derive_more::{Display, From};
 
 use binding_macro::{read, service, write};
 use protocol::traits::ExecutorParams;
-use protocol::traits::ServiceSDK;
+use protocol::traits::{ServiceSDK, StoreMap};
 use protocol::types::{Address, Hash, ServiceContext};
 use protocol::{Bytes, BytesMut, ProtocolError, ProtocolErrorKind, ProtocolResult};
 
 use crate::types::{
-    Contract, DeployPayload, DeployResp, ExecPayload, GetContractPayload, GetContractResp,
+    Contract, DeployFromCodeHashPayload, DeployPayload, DeployResp, ExecPayload,
+    GetCodeExistsPayload, GetCodeExistsResp, GetContractPayload, GetContractResp, UpgradeEvent,
+    UpgradePayload, UpgradeResp, UploadCodePayload, UploadCodeResp,
 };
 use crate::vm::{ChainInterface, Interpreter, InterpreterConf, InterpreterParams};
 
 pub struct RiscvService<SDK> {
-    sdk: Rc<RefCell<SDK>>,
+    sdk:   Rc<RefCell<SDK>>,
+    codes: Box<dyn StoreMap<Hash, Bytes>>,
 }
 
 #[service]
 impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
-    pub fn init(sdk: SDK) -> ProtocolResult<Self> {
+    pub fn init(mut sdk: SDK) -> ProtocolResult<Self> {
+        let codes: Box<dyn StoreMap<Hash, Bytes>> = sdk.alloc_or_recover_map("codes")?;
+
         Ok(Self {
             sdk: Rc::new(RefCell::new(sdk)),
+            codes,
         })
     }
 
@@ -42,10 +48,8 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
             .borrow()
             .get_value::<Address, Contract>(&payload.address)?
             .ok_or_else(|| ServiceError::ContractNotExists(payload.address.as_hex()))?;
-        let code: Bytes = self
-            .sdk
-            .borrow()
-            .get_value::<Hash, Bytes>(&contract.code_hash)?
+        let code = self
+            .get_code(&contract.code_hash)?
             .ok_or_else(|| ServiceError::CodeNotFound)?;
         let interpreter_params = InterpreterParams {
             address: payload.address.clone(),
@@ -78,6 +82,66 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
         Ok(ret)
     }
 
+    fn deploy_contract(
+        &mut self,
+        ctx: ServiceContext,
+        contract: Contract,
+        init_args: String,
+    ) -> ProtocolResult<DeployResp> {
+        let tx_hash = ctx
+            .get_tx_hash()
+            .ok_or_else(|| ServiceError::NotInExecContext("riscv deploy".to_owned()))?;
+
+        let contract_address =
+            Address::from_bytes(Hash::digest(tx_hash.as_bytes()).as_bytes().slice(0..20))?;
+
+        self.sdk
+            .borrow_mut()
+            .set_value(contract_address.clone(), contract)?;
+
+        // run init
+        let init_ret = if !init_args.is_empty() {
+            let init_payload = ExecPayload {
+                address: contract_address.clone(),
+                args:    init_args,
+            };
+
+            self.run(ctx, init_payload, true)?
+        } else {
+            String::new()
+        };
+
+        Ok(DeployResp {
+            address: contract_address,
+            init_ret,
+        })
+    }
+
+    // Code deployed before the `codes` map existed sits under its bare hash,
+    // the same keyspace as contract storage, so a value found there only
+    // counts as code if it hashes back to `code_hash`.
+    fn get_code(&self, code_hash: &Hash) -> ProtocolResult<Option<Bytes>> {
+        let code = if self.codes.contains(code_hash)? {
+            Some(self.codes.get(code_hash)?)
+        } else {
+            self.sdk.borrow().get_value::<Hash, Bytes>(code_hash)?
+        };
+
+        Ok(code.filter(|code| &Hash::digest(code.clone()) == code_hash))
+    }
+
+    fn save_code(&mut self, ctx: &ServiceContext, code: &str) -> ProtocolResult<Hash> {
+        let code = Bytes::from(hex::decode(code).map_err(ServiceError::HexDecode)?);
+
+        let code_hash = Hash::digest(code.clone());
+        let code_len = code.len() as u64;
+        // Every bytes cost 10 cycles
+        ctx.sub_cycles(code_len * 10)?;
+        self.codes.insert(code_hash.clone(), code)?;
+
+        Ok(code_hash)
+    }
+
     #[read]
     fn call(&self, ctx: ServiceContext, payload: ExecPayload) -> ProtocolResult<String> {
         self.run(ctx, payload, false)
@@ -94,44 +158,111 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
         ctx: ServiceContext,
         payload: DeployPayload,
     ) -> ProtocolResult<DeployResp> {
-        let code = Bytes::from(hex::decode(&payload.code).map_err(ServiceError::HexDecode)?);
+        let code_hash = self.save_code(&ctx, &payload.code)?;
+        let contract = Contract::new(code_hash, payload.intp_type, payload.admin);
 
-        // Save code
-        let code_hash = Hash::digest(code.clone());
-        let code_len = code.len() as u64;
-        // Every bytes cost 10 cycles
-        ctx.sub_cycles(code_len * 10)?;
-        self.sdk.borrow_mut().set_value(code_hash.clone(), code)?;
+        self.deploy_contract(ctx, contract, payload.init_args)
+    }
 
-        let tx_hash = ctx
-            .get_tx_hash()
-            .ok_or_else(|| ServiceError::NotInExecContext("riscv deploy".to_owned()))?;
+    // Deploys a new contract instance sharing code stored by an earlier
+    // `deploy` or `upload_code`, without paying for the code again.
+    #[write]
+    fn deploy_from_code_hash(
+        &mut self,
+        ctx: ServiceContext,
+        payload: DeployFromCodeHashPayload,
+    ) -> ProtocolResult<DeployResp> {
+        if self.get_code(&payload.code_hash)?.is_none() {
+            return Err(ServiceError::CodeNotFound.into());
+        }
+        let contract = Contract::new(payload.code_hash, payload.intp_type, payload.admin);
 
-        let contract_address =
-            Address::from_bytes(Hash::digest(tx_hash.as_bytes()).as_bytes().slice(0..20))?;
+        self.deploy_contract(ctx, contract, payload.init_args)
+    }
+
+    #[write]
+    fn upload_code(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UploadCodePayload,
+    ) -> ProtocolResult<UploadCodeResp> {
+        let code_hash = self.save_code(&ctx, &payload.code)?;
+
+        Ok(UploadCodeResp { code_hash })
+    }
+
+    #[read]
+    fn get_code_exists(
+        &self,
+        ctx: ServiceContext,
+        payload: GetCodeExistsPayload,
+    ) -> ProtocolResult<GetCodeExistsResp> {
+        ctx.sub_cycles(21000)?;
+        let exists = self.get_code(&payload.code_hash)?.is_some();
+
+        Ok(GetCodeExistsResp {
+            code_hash: payload.code_hash,
+            exists,
+        })
+    }
 
-        let intp_type = payload.intp_type;
-        let contract = Contract::new(code_hash, intp_type);
+    // Replaces the code of a contract while keeping its address and storage
+    #[write]
+    fn upgrade(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UpgradePayload,
+    ) -> ProtocolResult<UpgradeResp> {
+        let mut contract = self
+            .sdk
+            .borrow()
+            .get_value::<Address, Contract>(&payload.address)?
+            .ok_or_else(|| ServiceError::ContractNotExists(payload.address.as_hex()))?;
+
+        let admin = contract
+            .admin
+            .clone()
+            .ok_or_else(|| ServiceError::ContractNotUpgradable(payload.address.as_hex()))?;
+        if admin != ctx.get_caller() {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        let code_hash = self.save_code(&ctx, &payload.code)?;
+        let old_code_hash = std::mem::replace(&mut contract.code_hash, code_hash.clone());
+        if let Some(intp_type) = payload.intp_type {
+            contract.intp_type = intp_type;
+        }
+        let intp_type = contract.intp_type;
 
         self.sdk
             .borrow_mut()
-            .set_value(contract_address.clone(), contract)?;
+            .set_value(payload.address.clone(), contract)?;
 
-        // run init
-        let init_ret = if !payload.init_args.is_empty() {
-            let init_payload = ExecPayload {
-                address: contract_address.clone(),
-                args:    payload.init_args,
+        // run migration
+        let migrate_ret = if !payload.migrate_args.is_empty() {
+            let migrate_payload = ExecPayload {
+                address: payload.address.clone(),
+                args:    payload.migrate_args,
             };
 
-            self.run(ctx, init_payload, true)?
+            self.run(ctx.clone(), migrate_payload, true)?
         } else {
             String::new()
         };
 
-        Ok(DeployResp {
-            address: contract_address,
-            init_ret,
+        let event = UpgradeEvent {
+            address: payload.address,
+            admin,
+            old_code_hash,
+            new_code_hash: code_hash.clone(),
+            intp_type,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::Serde)?;
+        ctx.emit_event(event_str)?;
+
+        Ok(UpgradeResp {
+            code_hash,
+            migrate_ret,
         })
     }
 
@@ -150,13 +281,12 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
         let mut resp = GetContractResp {
             code_hash: contract.code_hash.clone(),
             intp_type: contract.intp_type,
+            admin: contract.admin.clone(),
             ..Default::default()
         };
         if payload.get_code {
             let code = self
-                .sdk
-                .borrow()
-                .get_value::<Hash, Bytes>(&contract.code_hash)?
+                .get_code(&contract.code_hash)?
                 .ok_or_else(|| ServiceError::CodeNotFound)?;
             ctx.sub_cycles(code.len() as u64)?;
             resp.code = hex::encode(&code);
@@ -282,6 +412,12 @@ pub enum ServiceError {
 
     #[display(fmt = "invalid key '{:?}', should be a hex string", _0)]
     InvalidKey(String),
+
+    #[display(fmt = "Contract {} has no admin and can not be upgraded", _0)]
+    ContractNotUpgradable(String),
+
+    #[display(fmt = "non authorized")]
+    NonAuthorized,
 }
 
 impl std::error::Error for ServiceError {}
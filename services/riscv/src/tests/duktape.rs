@@ -71,6 +71,7 @@ macro_rules! deploy_test_code {
             code:      hex::encode(Bytes::from(code)),
             intp_type: InterpreterType::Duktape,
             init_args: "".into(),
+            admin:     None,
         };
 
         let ret = service.deploy(context.make(), payload).expect("deploy");
@@ -89,6 +90,7 @@ fn should_support_pvm_init() {
         code:      hex::encode(Bytes::from(code)),
         intp_type: InterpreterType::Duktape,
         init_args: "do init".into(),
+        admin:     None,
     };
 
     let ret = service.deploy(context.make(), payload).expect("deploy");
@@ -185,6 +187,7 @@ fn should_support_pvm_origin() {
         code:      hex::encode(Bytes::from(code.as_ref())),
         intp_type: InterpreterType::Duktape,
         init_args: "".into(),
+        admin:     None,
     };
 
     let tc_ctx = context.make();
@@ -391,6 +394,7 @@ fn should_support_pvm_contract_call() {
         code:      hex::encode(Bytes::from(code.as_ref())),
         intp_type: InterpreterType::Duktape,
         init_args: "".into(),
+        admin:     None,
     };
 
     let tc_ctx = context.make();
@@ -422,6 +426,7 @@ fn should_support_pvm_service_call() {
         code:      hex::encode(Bytes::from(code.as_ref())),
         intp_type: InterpreterType::Duktape,
         init_args: "".into(),
+        admin:     None,
     };
 
     let tc_ctx = context.make();
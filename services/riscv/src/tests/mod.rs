@@ -10,13 +10,16 @@ use cita_trie::MemoryDB;
 
 use framework::binding::sdk::{DefalutServiceSDK, DefaultChainQuerier};
 use framework::binding::state::{GeneralServiceState, MPTTrie};
-use protocol::traits::{Dispatcher, ExecResp, Storage};
+use protocol::traits::{Dispatcher, ExecResp, ServiceSDK, Storage};
 use protocol::types::{
     Address, Block, Hash, Proof, Receipt, ServiceContext, ServiceContextParams, SignedTransaction,
 };
-use protocol::{Bytes, ProtocolResult};
+use protocol::{Bytes, BytesMut, ProtocolResult};
 
-use crate::types::{DeployPayload, ExecPayload, GetContractPayload, InterpreterType};
+use crate::types::{
+    DeployFromCodeHashPayload, DeployPayload, ExecPayload, GetCodeExistsPayload,
+    GetContractPayload, InterpreterType, UpgradeEvent, UpgradePayload, UploadCodePayload,
+};
 use crate::RiscvService;
 
 type TestRiscvService = RiscvService<
@@ -62,6 +65,7 @@ fn test_deploy_and_run() {
         code:      code.clone(),
         intp_type: InterpreterType::Binary,
         init_args: "set k init".into(),
+        admin:     None,
     };
     let deploy_result = service.deploy(context.clone(), deploy_payload).unwrap();
     assert_eq!(&deploy_result.init_ret, "");
@@ -115,6 +119,354 @@ fn test_deploy_and_run() {
     assert!(exec_result.is_err());
 }
 
+// Replaces the storage commands of simple_storage, reads are prefixed so the
+// new code shows through
+const UPGRADED_CODE: &str = r#"
+function main() {
+  if (PVM.is_init()) {
+    PVM.set_storage('m', PVM.load_args());
+    return 'migrated';
+  }
+  return 'v2 ' + PVM.get_storage(PVM.load_args());
+}
+"#;
+
+#[test]
+fn test_upgrade() {
+    let cycles_limit = 0x99_9999;
+    let admin = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, admin.clone(), tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let mut file = std::fs::File::open("src/tests/simple_storage").unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).unwrap();
+    let buffer = Bytes::from(buffer);
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:      hex::encode(buffer.as_ref()),
+            intp_type: InterpreterType::Binary,
+            init_args: "set k init".into(),
+            admin:     Some(admin.clone()),
+        })
+        .unwrap();
+    let address = deploy_result.address;
+
+    let upgrade_resp = service
+        .upgrade(context.clone(), UpgradePayload {
+            address:      address.clone(),
+            code:         hex::encode(UPGRADED_CODE),
+            intp_type:    Some(InterpreterType::Duktape),
+            migrate_args: "migrated".into(),
+        })
+        .unwrap();
+    let old_code_hash = Hash::digest(buffer);
+    let new_code_hash = Hash::digest(Bytes::from(UPGRADED_CODE));
+    assert_ne!(old_code_hash, new_code_hash);
+    assert_eq!(upgrade_resp.code_hash, new_code_hash);
+    assert_eq!(&upgrade_resp.migrate_ret, "migrated");
+
+    let events = context.get_events();
+    let event: UpgradeEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.address, address);
+    assert_eq!(event.admin, admin);
+    assert_eq!(event.old_code_hash, old_code_hash);
+    assert_eq!(event.new_code_hash, new_code_hash);
+    assert_eq!(event.intp_type as u8, InterpreterType::Duktape as u8);
+
+    // calls run the new code on the kept and migrated storage
+    let get_result = service.call(context.clone(), ExecPayload {
+        address: address.clone(),
+        args:    "k".into(),
+    });
+    assert_eq!(&get_result.unwrap(), "v2 init");
+    let get_result = service.call(context.clone(), ExecPayload {
+        address: address.clone(),
+        args:    "m".into(),
+    });
+    assert_eq!(&get_result.unwrap(), "v2 migrated");
+
+    let get_contract_resp = service
+        .get_contract(context, GetContractPayload {
+            address,
+            get_code: true,
+            storage_keys: vec![],
+        })
+        .unwrap();
+    assert_eq!(get_contract_resp.code_hash, new_code_hash);
+    assert_eq!(get_contract_resp.code, hex::encode(UPGRADED_CODE));
+    assert_eq!(get_contract_resp.admin, Some(admin));
+}
+
+#[test]
+fn test_upgrade_by_non_admin() {
+    let cycles_limit = 0x99_9999;
+    let admin = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let other = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let other_tx_hash =
+        Hash::from_hex("512a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, admin.clone(), tx_hash, nonce.clone());
+    let other_context = mock_context(cycles_limit, other, other_tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let mut file = std::fs::File::open("src/tests/simple_storage").unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).unwrap();
+    let buffer = Bytes::from(buffer);
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:      hex::encode(buffer.as_ref()),
+            intp_type: InterpreterType::Binary,
+            init_args: "set k init".into(),
+            admin:     Some(admin),
+        })
+        .unwrap();
+    let address = deploy_result.address;
+
+    let upgrade_res = service.upgrade(other_context.clone(), UpgradePayload {
+        address:      address.clone(),
+        code:         hex::encode(UPGRADED_CODE),
+        intp_type:    Some(InterpreterType::Duktape),
+        migrate_args: "".into(),
+    });
+    assert!(upgrade_res.is_err());
+    assert!(other_context.get_events().is_empty());
+
+    // the old code still runs
+    let get_contract_resp = service
+        .get_contract(context.clone(), GetContractPayload {
+            address:      address.clone(),
+            get_code:     false,
+            storage_keys: vec![],
+        })
+        .unwrap();
+    assert_eq!(get_contract_resp.code_hash, Hash::digest(buffer));
+    let get_result = service.call(context, ExecPayload {
+        address,
+        args: "get k".into(),
+    });
+    assert_eq!(&get_result.unwrap(), "init");
+}
+
+#[test]
+fn test_upgrade_without_admin() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let mut file = std::fs::File::open("src/tests/simple_storage").unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).unwrap();
+    let code = hex::encode(buffer);
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:      code.clone(),
+            intp_type: InterpreterType::Binary,
+            init_args: "".into(),
+            admin:     None,
+        })
+        .unwrap();
+
+    // a contract stored before admins were introduced, without the admin field
+    let legacy_address = Address::from_hex("0x888cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let code_hash = service
+        .upload_code(context.clone(), UploadCodePayload { code })
+        .unwrap()
+        .code_hash;
+    let mut legacy_contract = rlp::RlpStream::new_list(2);
+    legacy_contract
+        .append(&code_hash)
+        .append(&(InterpreterType::Binary as u8));
+    service
+        .sdk
+        .borrow_mut()
+        .set_value(legacy_address.clone(), Bytes::from(legacy_contract.out()))
+        .unwrap();
+
+    let get_contract_resp = service
+        .get_contract(context.clone(), GetContractPayload {
+            address:      legacy_address.clone(),
+            get_code:     false,
+            storage_keys: vec![],
+        })
+        .unwrap();
+    assert_eq!(get_contract_resp.admin, None);
+
+    for address in vec![deploy_result.address, legacy_address] {
+        let upgrade_res = service.upgrade(context.clone(), UpgradePayload {
+            address,
+            code: hex::encode(UPGRADED_CODE),
+            intp_type: Some(InterpreterType::Duktape),
+            migrate_args: "".into(),
+        });
+        assert!(upgrade_res.is_err());
+    }
+    assert!(context.get_events().is_empty());
+}
+
+#[test]
+fn test_deploy_from_code_hash() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let tx_hashes: Vec<Hash> = vec![
+        "412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828",
+        "512a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828",
+        "612a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828",
+        "712a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828",
+    ]
+    .into_iter()
+    .map(|h| Hash::from_hex(h).unwrap())
+    .collect();
+    let contexts: Vec<ServiceContext> = tx_hashes
+        .into_iter()
+        .map(|tx_hash| mock_context(cycles_limit, caller.clone(), tx_hash, nonce.clone()))
+        .collect();
+
+    let mut service = new_riscv_service();
+
+    let mut file = std::fs::File::open("src/tests/simple_storage").unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).unwrap();
+    let buffer = Bytes::from(buffer);
+    let code_hash = Hash::digest(buffer.clone());
+
+    let exists_resp = service
+        .get_code_exists(contexts[0].clone(), GetCodeExistsPayload {
+            code_hash: code_hash.clone(),
+        })
+        .unwrap();
+    assert!(!exists_resp.exists);
+
+    let deploy_res =
+        service.deploy_from_code_hash(contexts[0].clone(), DeployFromCodeHashPayload {
+            code_hash: code_hash.clone(),
+            intp_type: InterpreterType::Binary,
+            init_args: "".into(),
+            admin:     None,
+        });
+    assert!(deploy_res.is_err());
+
+    let upload_resp = service
+        .upload_code(contexts[0].clone(), UploadCodePayload {
+            code: hex::encode(buffer.as_ref()),
+        })
+        .unwrap();
+    assert_eq!(upload_resp.code_hash, code_hash);
+
+    let exists_resp = service
+        .get_code_exists(contexts[0].clone(), GetCodeExistsPayload {
+            code_hash: code_hash.clone(),
+        })
+        .unwrap();
+    assert!(exists_resp.exists);
+
+    // two instances share the code but not the storage
+    let mut addresses = vec![];
+    for (context, value) in contexts[1..3].iter().zip(&["a", "b"]) {
+        let deploy_resp = service
+            .deploy_from_code_hash(context.clone(), DeployFromCodeHashPayload {
+                code_hash: code_hash.clone(),
+                intp_type: InterpreterType::Binary,
+                init_args: format!("set k {}", value),
+                admin:     None,
+            })
+            .unwrap();
+        addresses.push(deploy_resp.address);
+    }
+    assert_ne!(addresses[0], addresses[1]);
+
+    // only the upload pays for the code
+    service
+        .deploy(contexts[3].clone(), DeployPayload {
+            code:      hex::encode(buffer.as_ref()),
+            intp_type: InterpreterType::Binary,
+            init_args: "set k a".into(),
+            admin:     None,
+        })
+        .unwrap();
+    assert!(contexts[1].get_cycles_used() < contexts[3].get_cycles_used());
+
+    for (address, value) in addresses.into_iter().zip(&["a", "b"]) {
+        let get_contract_resp = service
+            .get_contract(contexts[0].clone(), GetContractPayload {
+                address:      address.clone(),
+                get_code:     false,
+                storage_keys: vec![],
+            })
+            .unwrap();
+        assert_eq!(get_contract_resp.code_hash, code_hash);
+
+        let get_result = service.call(contexts[0].clone(), ExecPayload {
+            address,
+            args: "get k".into(),
+        });
+        assert_eq!(&get_result.unwrap(), value);
+    }
+}
+
+#[test]
+fn test_deploy_from_storage_hash() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let mut file = std::fs::File::open("src/tests/simple_storage").unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).unwrap();
+    let deploy_resp = service
+        .deploy(context.clone(), DeployPayload {
+            code:      hex::encode(buffer),
+            intp_type: InterpreterType::Binary,
+            init_args: "set k init".into(),
+            admin:     None,
+        })
+        .unwrap();
+
+    // the hash a storage slot is kept under is not a code hash
+    let mut slot_key = BytesMut::from(deploy_resp.address.as_bytes().as_ref());
+    slot_key.extend_from_slice(b"k");
+    let slot_hash = Hash::digest(slot_key.freeze());
+
+    let exists_resp = service
+        .get_code_exists(context.clone(), GetCodeExistsPayload {
+            code_hash: slot_hash.clone(),
+        })
+        .unwrap();
+    assert!(!exists_resp.exists);
+
+    let deploy_res = service.deploy_from_code_hash(context, DeployFromCodeHashPayload {
+        code_hash: slot_hash,
+        intp_type: InterpreterType::Binary,
+        init_args: "".into(),
+        admin:     None,
+    });
+    assert!(deploy_res.is_err());
+}
+
 struct MockDispatcher;
 
 impl Dispatcher for MockDispatcher {
@@ -42,6 +42,9 @@ pub struct DeployPayload {
     #[serde(default)]
     pub intp_type: InterpreterType,
     pub init_args: String,
+    // The only address allowed to upgrade the contract, none makes it immutable
+    #[serde(default)]
+    pub admin:     Option<Address>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -69,10 +72,69 @@ pub struct InterpreterResult {
     pub ret_code:    i8,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DeployFromCodeHashPayload {
+    pub code_hash: Hash,
+    #[serde(default)]
+    pub intp_type: InterpreterType,
+    pub init_args: String,
+    #[serde(default)]
+    pub admin:     Option<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UploadCodePayload {
+    pub code: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UploadCodeResp {
+    pub code_hash: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetCodeExistsPayload {
+    pub code_hash: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetCodeExistsResp {
+    pub code_hash: Hash,
+    pub exists:    bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpgradePayload {
+    pub address:      Address,
+    pub code:         String,
+    // Keeps the current interpreter if not given
+    #[serde(default)]
+    pub intp_type:    Option<InterpreterType>,
+    // The new code runs with these args as init if not empty
+    #[serde(default)]
+    pub migrate_args: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpgradeResp {
+    pub code_hash:   Hash,
+    pub migrate_ret: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpgradeEvent {
+    pub address:       Address,
+    pub admin:         Address,
+    pub old_code_hash: Hash,
+    pub new_code_hash: Hash,
+    pub intp_type:     InterpreterType,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, Constructor)]
 pub struct Contract {
     pub code_hash: Hash,
     pub intp_type: InterpreterType,
+    pub admin:     Option<Address>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -88,6 +150,7 @@ pub struct GetContractPayload {
 pub struct GetContractResp {
     pub code_hash:      Hash,
     pub intp_type:      InterpreterType,
+    pub admin:          Option<Address>,
     pub code:           String,
     pub storage_values: Vec<String>,
 }
@@ -104,9 +167,10 @@ impl FixedCodec for Contract {
 
 impl rlp::Encodable for Contract {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(2)
+        s.begin_list(3)
             .append(&self.code_hash)
-            .append(&(self.intp_type as u8));
+            .append(&(self.intp_type as u8))
+            .append(&self.admin);
     }
 }
 
@@ -114,10 +178,17 @@ impl rlp::Decodable for Contract {
     fn decode(r: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         let code_hash: Hash = r.val_at(0)?;
         let intp_type: u8 = r.val_at(1)?;
+        // Contracts deployed before admins were introduced have no admin
+        let admin: Option<Address> = if r.item_count()? > 2 {
+            r.val_at(2)?
+        } else {
+            None
+        };
 
         Ok(Contract {
             code_hash,
             intp_type: InterpreterType::try_from(intp_type).map_err(rlp::DecoderError::Custom)?,
+            admin,
         })
     }
 }
@@ -122,7 +122,7 @@ describe("asset service API test via muta-sdk-js", () => {
         expect(gaRet.id).toBe(assetID);
         expect(gaRet.name).toBe("Test Token");
         expect(gaRet.symbol).toBe("TT");
-        expect(gaRet.supply).toBe(8888);
+        expect(gaRet.supply).toBe("8888");
         expect('0x' + gaRet.issuer).toBe(accounts[0].address);
 
         // Transfer
@@ -136,8 +136,8 @@ describe("asset service API test via muta-sdk-js", () => {
         const issuerBalance = JSON.parse(issuerBalanceRes.ret).balance;
         let recipientBalanceRes = await getBalance(assetID, accounts[1].address);
         let recipientBalance = JSON.parse(recipientBalanceRes.ret).balance;
-        expect(issuerBalance).toBe(8800);
-        expect(recipientBalance).toBe(88);
+        expect(issuerBalance).toBe("8800");
+        expect(recipientBalance).toBe("88");
 
         // Approve
         const apprReceipt = await approve(accounts[1], assetID, accounts[2].address, 8);
@@ -146,7 +146,7 @@ describe("asset service API test via muta-sdk-js", () => {
         // Check allowance
         let alloRes = await getAllowance(assetID, accounts[1].address, accounts[2].address);
         let allowance = JSON.parse(alloRes.ret).value;
-        expect(allowance).toBe(8);
+        expect(allowance).toBe("8");
 
         // Transfer from
         const tfReceipt = await transferFrom(accounts[2], assetID, accounts[1].address, accounts[2].address, 8);
@@ -157,10 +157,10 @@ describe("asset service API test via muta-sdk-js", () => {
         const senderBalance = JSON.parse(senderBalanceRes.ret).balance;
         recipientBalanceRes = await getBalance(assetID, accounts[2].address);
         recipientBalance = JSON.parse(recipientBalanceRes.ret).balance;
-        expect(senderBalance).toBe(80);
-        expect(recipientBalance).toBe(8);
+        expect(senderBalance).toBe("80");
+        expect(recipientBalance).toBe("8");
         alloRes = await getAllowance(assetID, accounts[1].address, accounts[2].address);
         allowance = JSON.parse(alloRes.ret).value;
-        expect(allowance).toBe(0);
+        expect(allowance).toBe("0");
     });
 });
\ No newline at end of file